@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::game::data::load_ron_dir;
+use crate::game::decoration::{DecorationRegistry, DecorationType};
+
+// Индекс биома в реестре. Биомы сортируются по имени,
+// так что индекс не зависит от порядка файлов на диске
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BiomeType(pub u16);
+
+// Виды земли, которые биом может разбрасывать по своей территории
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum GroundKind {
+    Grass,
+    Dirt,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TileWeight {
+    pub ground: GroundKind,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BiomeTextures {
+    pub grass: Vec<String>,
+    pub water: String,
+    #[serde(default)]
+    pub dirt: Option<String>,
+    // Берег рисуется водой вправо, как и переходы между биомами
+    #[serde(default)]
+    pub shore: Option<BorderTexture>,
+}
+
+// rotation поворачивает текстуру так, чтобы сторона соседнего биома смотрела вправо
+#[derive(Debug, Clone, Deserialize)]
+pub struct BorderTexture {
+    pub texture: String,
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BiomeDecoration {
+    // Имя декорации из assets/decorations
+    pub decoration: String,
+    // Доля тайлов биома, на которых появляется декорация
+    pub density: f32,
+    // Минимальное расстояние до других декораций, в тайлах
+    pub min_distance: f32,
+    #[serde(skip)]
+    pub kind: DecorationType,
+}
+
+// В какое время суток работает запись таблицы появления
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum SpawnTime {
+    #[default]
+    Always,
+    Day,
+    Night,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BiomeSpawn {
+    // Имя врага из assets/enemies
+    pub enemy: String,
+    pub weight: u32,
+    #[serde(default)]
+    pub time: SpawnTime,
+    // Сколько врагов появляется вместе, от и до
+    #[serde(default = "default_group")]
+    pub group: (u32, u32),
+}
+
+fn default_group() -> (u32, u32) {
+    (1, 1)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BiomeDefinition {
+    pub name: String,
+    // Диапазоны шума, в которых появляется биом
+    pub temperature_range: (f32, f32),
+    pub humidity_range: (f32, f32),
+    // Климат внутри биома для игровых систем
+    pub temperature: f32,
+    // Пока ни одна система влажность не использует, но она часть формата биома
+    #[allow(dead_code)]
+    pub humidity: f32,
+    // Множитель скорости передвижения по земле биома
+    #[serde(default = "default_movement_speed")]
+    pub movement_speed: f32,
+    pub tiles: Vec<TileWeight>,
+    pub textures: BiomeTextures,
+    // Текстуры переходов по имени соседнего биома
+    #[serde(default)]
+    pub borders: HashMap<String, BorderTexture>,
+    #[serde(default)]
+    pub decorations: Vec<BiomeDecoration>,
+    // Таблица появления врагов
+    #[serde(default)]
+    pub spawns: Vec<BiomeSpawn>,
+}
+
+fn default_movement_speed() -> f32 {
+    1.0
+}
+
+impl BiomeDefinition {
+    fn contains(&self, temperature: f32, humidity: f32) -> bool {
+        let (t_min, t_max) = self.temperature_range;
+        let (h_min, h_max) = self.humidity_range;
+        (t_min..t_max).contains(&temperature) && (h_min..h_max).contains(&humidity)
+    }
+
+    fn distance(&self, temperature: f32, humidity: f32) -> f32 {
+        let t_center = (self.temperature_range.0 + self.temperature_range.1) * 0.5;
+        let h_center = (self.humidity_range.0 + self.humidity_range.1) * 0.5;
+        (t_center - temperature).powi(2) + (h_center - humidity).powi(2)
+    }
+
+    // chance в диапазоне 0..1 выбирает землю по таблице весов
+    pub fn roll_ground(&self, chance: f32) -> GroundKind {
+        let total: u32 = self.tiles.iter().map(|tile| tile.weight).sum();
+        let mut roll = chance * total as f32;
+
+        for tile in &self.tiles {
+            if roll < tile.weight as f32 {
+                return tile.ground;
+            }
+            roll -= tile.weight as f32;
+        }
+
+        GroundKind::Grass
+    }
+
+    pub fn grass_texture(&self, variant: u8) -> &str {
+        &self.textures.grass[variant as usize % self.textures.grass.len()]
+    }
+
+    pub fn dirt_texture(&self) -> &str {
+        self.textures.dirt.as_deref().unwrap_or_else(|| self.grass_texture(0))
+    }
+
+    // chance в диапазоне 0..1 выбирает врага по весам среди записей, подходящих ко времени суток
+    pub fn pick_spawn(&self, night: bool, chance: f32) -> Option<&BiomeSpawn> {
+        let active = |spawn: &&BiomeSpawn| match spawn.time {
+            SpawnTime::Always => true,
+            SpawnTime::Day => !night,
+            SpawnTime::Night => night,
+        };
+        let total: u32 = self.spawns.iter().filter(active).map(|spawn| spawn.weight).sum();
+        let mut roll = chance * total as f32;
+
+        for spawn in self.spawns.iter().filter(active) {
+            if roll < spawn.weight as f32 {
+                return Some(spawn);
+            }
+            roll -= spawn.weight as f32;
+        }
+        None
+    }
+
+    // roll в диапазоне 0..1 попадает в долю одной из декораций или мимо всех
+    pub fn pick_decoration(&self, roll: f32) -> Option<&BiomeDecoration> {
+        let mut roll = roll;
+        for decoration in &self.decorations {
+            if roll < decoration.density {
+                return Some(decoration);
+            }
+            roll -= decoration.density;
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BiomeRegistry {
+    biomes: Vec<BiomeDefinition>,
+    borders: HashMap<(BiomeType, BiomeType), BorderTexture>,
+}
+
+impl BiomeRegistry {
+    pub fn load(dir: &Path, decorations: &DecorationRegistry) -> Result<Self, String> {
+        Self::from_definitions(load_ron_dir(dir)?, decorations)
+    }
+
+    pub fn from_definitions(
+        mut biomes: Vec<BiomeDefinition>,
+        decorations: &DecorationRegistry,
+    ) -> Result<Self, String> {
+        if biomes.is_empty() {
+            return Err("не задано ни одного биома".to_string());
+        }
+        if biomes.len() > u16::MAX as usize {
+            return Err(format!("слишком много биомов: {}", biomes.len()));
+        }
+        biomes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for biome in &mut biomes {
+            for entry in &mut biome.decorations {
+                entry.kind = decorations.find(&entry.decoration)
+                    .ok_or_else(|| format!("биом {}: неизвестная декорация {}", biome.name, entry.decoration))?;
+            }
+        }
+
+        let find = |name: &str| {
+            biomes
+                .iter()
+                .position(|biome| biome.name == name)
+                .map(|index| BiomeType(index as u16))
+        };
+
+        let mut borders = HashMap::new();
+        for (index, biome) in biomes.iter().enumerate() {
+            if index > 0 && biomes[index - 1].name == biome.name {
+                return Err(format!("биом {} объявлен дважды", biome.name));
+            }
+            if biome.textures.grass.is_empty() {
+                return Err(format!("у биома {} нет текстур травы", biome.name));
+            }
+            if biome.tiles.iter().all(|tile| tile.weight == 0) {
+                return Err(format!("у биома {} пустая таблица тайлов", biome.name));
+            }
+            let has_dirt = biome.tiles.iter().any(|tile| tile.ground == GroundKind::Dirt && tile.weight > 0);
+            if has_dirt && biome.textures.dirt.is_none() {
+                return Err(format!("у биома {} есть земля, но нет её текстуры", biome.name));
+            }
+            if let Some(spawn) = biome.spawns.iter().find(|spawn| spawn.group.0 == 0 || spawn.group.0 > spawn.group.1) {
+                return Err(format!("биом {}: неверный размер группы {}", biome.name, spawn.enemy));
+            }
+
+            for (neighbour, border) in &biome.borders {
+                let to = find(neighbour)
+                    .ok_or_else(|| format!("биом {}: неизвестный сосед {}", biome.name, neighbour))?;
+                borders.insert((BiomeType(index as u16), to), border.clone());
+            }
+        }
+
+        Ok(Self { biomes, borders })
+    }
+
+    pub fn get(&self, biome: BiomeType) -> &BiomeDefinition {
+        &self.biomes[biome.0 as usize]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BiomeDefinition> {
+        self.biomes.iter()
+    }
+
+    // Если точка не попала ни в один диапазон, берём биом с ближайшим центром
+    pub fn determine_biome(&self, temperature: f32, humidity: f32) -> BiomeType {
+        let index = self
+            .biomes
+            .iter()
+            .position(|biome| biome.contains(temperature, humidity))
+            .unwrap_or_else(|| {
+                self.biomes
+                    .iter()
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| {
+                        a.distance(temperature, humidity).total_cmp(&b.distance(temperature, humidity))
+                    })
+                    .map(|(index, _)| index)
+                    .unwrap_or(0)
+            });
+
+        BiomeType(index as u16)
+    }
+
+    pub fn border_texture(&self, from: BiomeType, to: BiomeType) -> Option<&BorderTexture> {
+        self.borders.get(&(from, to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn_biome() -> BiomeDefinition {
+        ron::from_str(
+            r#"(
+                name: "test",
+                temperature_range: (0.0, 1.0),
+                humidity_range: (0.0, 1.0),
+                temperature: 0.5,
+                humidity: 0.5,
+                tiles: [(ground: Grass, weight: 1)],
+                textures: (grass: ["grass.png"], water: "water.png"),
+                spawns: [
+                    (enemy: "boar", weight: 3, time: Day),
+                    (enemy: "rat", weight: 1),
+                    (enemy: "wolf", weight: 1, time: Night),
+                ],
+            )"#,
+        )
+        .unwrap()
+    }
+
+    fn pick(biome: &BiomeDefinition, night: bool, chance: f32) -> &str {
+        biome.pick_spawn(night, chance).map(|spawn| spawn.enemy.as_str()).unwrap_or("-")
+    }
+
+    #[test]
+    fn spawns_filtered_by_time_of_day() {
+        let biome = spawn_biome();
+        for chance in [0.0, 0.25, 0.5, 0.75, 0.99] {
+            assert_ne!(pick(&biome, false, chance), "wolf", "ночной враг не появляется днём");
+            assert_ne!(pick(&biome, true, chance), "boar", "дневной враг не появляется ночью");
+        }
+    }
+
+    #[test]
+    fn spawn_weights_split_roll() {
+        let biome = spawn_biome();
+        // Днём веса 3 и 1: граница на 0.75
+        assert_eq!(pick(&biome, false, 0.0), "boar");
+        assert_eq!(pick(&biome, false, 0.74), "boar");
+        assert_eq!(pick(&biome, false, 0.75), "rat");
+        assert_eq!(pick(&biome, false, 0.99), "rat");
+        // Ночью веса 1 и 1: граница на 0.5
+        assert_eq!(pick(&biome, true, 0.49), "rat");
+        assert_eq!(pick(&biome, true, 0.5), "wolf");
+        assert_eq!(pick(&biome, true, 1.0), "-", "бросок за пределами весов ничего не выбирает");
+    }
+}
@@ -0,0 +1,420 @@
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use bevy::input::mouse::MouseWheel;
+use crate::game::bullet::CursorPosition;
+use crate::game::collision::Collider;
+use crate::game::health::{DamageEvent, Dead, Health};
+use crate::game::inventory::{spawn_world_item, Inventory, IMMEDIATE_PICKUP};
+use crate::game::item::{ItemRegistry, ItemStack};
+use crate::game::map::{ChunkModified, MapState, YSort};
+use crate::game::menu::GameState;
+use crate::game::player::Player;
+use crate::game::station::CraftingStation;
+use crate::game::structure::{StructureLayer, StructureRegistry, StructureType};
+use crate::game::tile_query::TileQuery;
+use crate::game::world::{TilePos, TILE_SIZE};
+
+// Как далеко от игрока можно строить и разбирать, в мировых единицах
+const BUILD_REACH: f32 = TILE_SIZE * 4.0;
+// Пол лежит прямо на земле, под всеми отсортированными спрайтами
+const FLOOR_Z: f32 = 0.5;
+// Призрак рисуется поверх построек, но под пулями
+const GHOST_Z: f32 = 15.0;
+const GHOST_VALID_COLOR: Color = Color::rgba(0.5, 1.0, 0.5, 0.6);
+const GHOST_INVALID_COLOR: Color = Color::rgba(1.0, 0.4, 0.4, 0.6);
+
+#[derive(Component)]
+pub struct Structure {
+    pub kind: StructureType,
+}
+
+// Полупрозрачная постройка под курсором в режиме строительства
+#[derive(Component)]
+pub struct BuildGhost;
+
+#[derive(Component)]
+pub struct BuildHud;
+
+// Что выбрано для строительства. Сам режим включается флагом GameState::building
+#[derive(Resource, Default)]
+pub struct BuildMode {
+    pub selected: StructureType,
+}
+
+pub fn spawn_structure(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    registry: &StructureRegistry,
+    kind: StructureType,
+    position: TilePos,
+    health: f32,
+) -> Entity {
+    let definition = registry.get(kind);
+    let position = position.to_world();
+    let mut entity = commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_xyz(position.x, position.y, FLOOR_Z),
+            texture: asset_server.load(definition.texture.as_str()),
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(TILE_SIZE)),
+                ..default()
+            },
+            ..default()
+        },
+        Structure { kind },
+        Health {
+            current: health.min(definition.health),
+            max: definition.health,
+        },
+    ));
+
+    if definition.layer == StructureLayer::Object {
+        entity.insert(YSort);
+    }
+    if let Some(radius) = definition.collision_radius {
+        entity.insert(Collider { radius: radius * TILE_SIZE });
+    }
+    entity.id()
+}
+
+// Правила для самого тайла: он в досягаемости, загружен (walkable не None) и проходим
+fn check_tile(center: Vec2, player: Vec2, walkable: Option<bool>) -> Result<(), String> {
+    if center.distance(player) > BUILD_REACH {
+        return Err("слишком далеко".to_string());
+    }
+    match walkable {
+        Some(true) => Ok(()),
+        Some(false) => Err("на воде строить нельзя".to_string()),
+        None => Err("чанк не загружен".to_string()),
+    }
+}
+
+// Всё, что нужно, чтобы проверить место под постройку, поставить её и разобрать
+#[derive(SystemParam)]
+pub struct BuildSite<'w, 's> {
+    tiles: TileQuery<'w, 's>,
+    asset_server: Res<'w, AssetServer>,
+    items: Res<'w, ItemRegistry>,
+    structure_query: Query<'w, 's, (Entity, &'static Structure, &'static Health, &'static Parent, &'static GlobalTransform)>,
+    collider_query: Query<'w, 's, (&'static GlobalTransform, &'static Collider), Without<Structure>>,
+    station_query: Query<'w, 's, &'static GlobalTransform, With<CraftingStation>>,
+}
+
+impl BuildSite<'_, '_> {
+    pub fn structures(&self) -> &StructureRegistry {
+        self.tiles.map_state().structures()
+    }
+
+    // Постройка заданного слоя на тайле
+    fn structure_at(&self, tile: TilePos, layer: StructureLayer) -> Option<(Entity, StructureType, f32, Entity)> {
+        self.structure_query
+            .iter()
+            .find(|(_, structure, _, _, transform)| {
+                TilePos::from_world(transform.translation().truncate()) == tile
+                    && self.structures().get(structure.kind).layer == layer
+            })
+            .map(|(entity, structure, health, parent, _)| (entity, structure.kind, health.current, parent.get()))
+    }
+
+    pub fn map_state(&self) -> &MapState {
+        self.tiles.map_state()
+    }
+
+    pub fn asset_server(&self) -> &AssetServer {
+        &self.asset_server
+    }
+
+    // Почему на этот тайл нельзя поставить что-то слоя layer с радиусом столкновения radius в тайлах.
+    // Годится и для построек, и для станций: станции стоят в слое предметов
+    pub fn check_place(&self, tile: TilePos, player: Vec2, layer: StructureLayer, radius: Option<f32>) -> Result<(), String> {
+        let center = tile.to_world();
+        check_tile(center, player, self.tiles.tile(tile).map(|info| info.walkable))?;
+        if self.structure_at(tile, layer).is_some() {
+            return Err("место занято".to_string());
+        }
+        let station = self
+            .station_query
+            .iter()
+            .any(|transform| TilePos::from_world(transform.translation().truncate()) == tile);
+        if layer == StructureLayer::Object && station {
+            return Err("место занято".to_string());
+        }
+        // Твёрдое не должно налезать на декорации, станции и живых, в том числе на самого игрока
+        if let Some(radius) = radius {
+            let blocked = self.collider_query.iter().any(|(transform, collider)| {
+                transform.translation().truncate().distance(center) < radius * TILE_SIZE + collider.radius
+            });
+            if blocked {
+                return Err("место занято".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // Почему постройку нельзя поставить игроку в точке player на этот тайл
+    pub fn check(&self, kind: StructureType, tile: TilePos, player: Vec2, inventory: &Inventory) -> Result<(), String> {
+        let definition = self.structures().get(kind);
+        self.check_place(tile, player, definition.layer, definition.collision_radius)?;
+
+        let cost = definition.cost(&self.items)?;
+        if cost.iter().any(|stack| inventory.count(stack.item) < stack.count) {
+            return Err("не хватает материалов".to_string());
+        }
+        Ok(())
+    }
+
+    // Списывает материалы и ставит постройку. Место должно быть проверено через check
+    fn build(&self, commands: &mut Commands, kind: StructureType, tile: TilePos, inventory: &mut Inventory) -> Result<(), String> {
+        let definition = self.structures().get(kind);
+        let chunk = self
+            .tiles
+            .map_state()
+            .chunk_entity(tile.chunk())
+            .ok_or_else(|| "чанк не загружен".to_string())?;
+
+        for stack in definition.cost(&self.items)? {
+            inventory.remove(stack.item, stack.count);
+        }
+        let entity = spawn_structure(commands, &self.asset_server, self.structures(), kind, tile, definition.health);
+        commands.entity(entity).set_parent(chunk);
+        commands.entity(chunk).insert(ChunkModified);
+        Ok(())
+    }
+
+    // Разбирает верхнюю постройку на тайле и возвращает часть материалов
+    fn deconstruct(&self, commands: &mut Commands, tile: TilePos, inventory: &mut Inventory) -> Result<(), String> {
+        let Some((entity, kind, health, chunk)) = self
+            .structure_at(tile, StructureLayer::Object)
+            .or_else(|| self.structure_at(tile, StructureLayer::Floor))
+        else {
+            return Ok(());
+        };
+
+        for stack in self.structures().get(kind).refund(&self.items, health)? {
+            let left = inventory.add(&self.items, stack);
+            // То, что не влезло в инвентарь, падает на землю
+            if left > 0 {
+                let stack = ItemStack { item: stack.item, count: left };
+                spawn_world_item(commands, &self.asset_server, &self.items, stack, tile.to_world(), IMMEDIATE_PICKUP);
+            }
+        }
+        commands.entity(entity).despawn_recursive();
+        commands.entity(chunk).insert(ChunkModified);
+        Ok(())
+    }
+}
+
+// Стоимость построек проверяется после загрузки предметов
+pub fn setup_building(mut commands: Commands, map_state: Res<MapState>, items: Res<ItemRegistry>) {
+    for definition in map_state.structures().iter() {
+        if let Err(err) = definition.cost(&items) {
+            panic!("Не удалось загрузить постройки: {}", err);
+        }
+    }
+    commands.init_resource::<BuildMode>();
+
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(TILE_SIZE)),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        BuildGhost,
+    ));
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            bottom: Val::Px(40.0),
+            ..default()
+        }),
+        BuildHud,
+    ));
+}
+
+// B включает и выключает режим строительства, колесо мыши в нём выбирает постройку
+pub fn build_mode_input(
+    keyboard: Res<Input<KeyCode>>,
+    mut wheel_events: EventReader<MouseWheel>,
+    map_state: Res<MapState>,
+    mut game_state: ResMut<GameState>,
+    mut build_mode: ResMut<BuildMode>,
+) {
+    let scroll: f32 = wheel_events.iter().map(|event| event.y).sum();
+    if game_state.paused {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::B) {
+        game_state.building = !game_state.building;
+    }
+
+    // После перезагрузки построек выбранный индекс мог выйти за границы. Системы, которые
+    // берут выбранную постройку из реестра, стоят в цепочке после этой
+    let count = map_state.structures().count() as i32;
+    if count == 0 {
+        game_state.building = false;
+        return;
+    }
+    let step = if !game_state.building || scroll == 0.0 {
+        0
+    } else if scroll > 0.0 {
+        1
+    } else {
+        -1
+    };
+    let next = StructureType((build_mode.selected.0 as i32 + step).rem_euclid(count) as u16);
+    if next != build_mode.selected {
+        build_mode.selected = next;
+    }
+}
+
+// Призрак прилипает к тайлу под курсором и краснеет, если там строить нельзя
+pub fn update_ghost(
+    game_state: Res<GameState>,
+    build_mode: Res<BuildMode>,
+    cursor: CursorPosition,
+    site: BuildSite,
+    player_query: Query<(&Transform, &Inventory), (With<Player>, Without<Dead>)>,
+    mut ghost_query: Query<(&mut Transform, &mut Sprite, &mut Handle<Image>, &mut Visibility), (With<BuildGhost>, Without<Player>)>,
+) {
+    let Ok((mut transform, mut sprite, mut texture, mut visibility)) = ghost_query.get_single_mut() else {
+        return;
+    };
+    let target = player_query
+        .get_single()
+        .ok()
+        .zip(cursor.world_position())
+        .filter(|_| game_state.building && !game_state.paused && !game_state.pointer_over_ui);
+    let Some(((player_transform, inventory), cursor)) = target else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    let tile = TilePos::from_world(cursor);
+    let position = tile.to_world();
+    transform.translation = position.extend(GHOST_Z);
+    *visibility = Visibility::Inherited;
+    let wanted = site.asset_server.load(site.structures().get(build_mode.selected).texture.as_str());
+    if *texture != wanted {
+        *texture = wanted;
+    }
+
+    let valid = site
+        .check(build_mode.selected, tile, player_transform.translation.truncate(), inventory)
+        .is_ok();
+    sprite.color = if valid { GHOST_VALID_COLOR } else { GHOST_INVALID_COLOR };
+}
+
+// Левая кнопка ставит выбранную постройку, правая разбирает постройку под курсором
+pub fn build_input(
+    mut commands: Commands,
+    mouse: Res<Input<MouseButton>>,
+    game_state: Res<GameState>,
+    build_mode: Res<BuildMode>,
+    cursor: CursorPosition,
+    site: BuildSite,
+    mut player_query: Query<(&Transform, &mut Inventory), (With<Player>, Without<Dead>)>,
+) {
+    if !game_state.building || game_state.paused || game_state.pointer_over_ui {
+        return;
+    }
+    let Ok((transform, mut inventory)) = player_query.get_single_mut() else {
+        return;
+    };
+    let Some(cursor) = cursor.world_position() else {
+        return;
+    };
+    let tile = TilePos::from_world(cursor);
+    let position = transform.translation.truncate();
+
+    if mouse.just_pressed(MouseButton::Left) {
+        let result = site
+            .check(build_mode.selected, tile, position, &inventory)
+            .and_then(|_| site.build(&mut commands, build_mode.selected, tile, &mut inventory));
+        if let Err(err) = result {
+            println!("Нельзя построить {}: {}", site.structures().get(build_mode.selected).title, err);
+        }
+    } else if mouse.just_pressed(MouseButton::Right) {
+        if tile.to_world().distance(position) > BUILD_REACH {
+            return;
+        }
+        if let Err(err) = site.deconstruct(&mut commands, tile, &mut inventory) {
+            println!("Ошибка разборки: {}", err);
+        }
+    }
+}
+
+// Повреждённая постройка сохраняется вместе с чанком, разрушенная пропадает из него
+pub fn mark_damaged_structures(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageEvent>,
+    structure_query: Query<&Parent, With<Structure>>,
+) {
+    for event in damage_events.iter() {
+        if let Ok(parent) = structure_query.get(event.target) {
+            commands.entity(parent.get()).insert(ChunkModified);
+        }
+    }
+}
+
+pub fn build_hud(
+    game_state: Res<GameState>,
+    build_mode: Res<BuildMode>,
+    site: BuildSite,
+    mut hud_query: Query<&mut Text, With<BuildHud>>,
+) {
+    let value = if game_state.building {
+        let definition = site.structures().get(build_mode.selected);
+        let cost: Vec<String> = definition
+            .cost(&site.items)
+            .unwrap_or_default()
+            .iter()
+            .map(|stack| format!("{} {}", site.items.get(stack.item).title, stack.count))
+            .collect();
+        format!(
+            "Стройка: {} ({})\nЛКМ - поставить, ПКМ - разобрать, колесо - выбор, B - выход",
+            definition.title,
+            cost.join(", "),
+        )
+    } else {
+        String::new()
+    };
+
+    for mut text in hud_query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_must_be_in_reach() {
+        let center = TilePos(3, -2).to_world();
+        assert!(check_tile(center, center + Vec2::new(BUILD_REACH, 0.0), Some(true)).is_ok());
+        assert!(check_tile(center, center + Vec2::new(BUILD_REACH + 1.0, 0.0), Some(true)).is_err());
+    }
+
+    #[test]
+    fn tile_must_be_loaded_and_walkable() {
+        let center = TilePos(0, 0).to_world();
+        assert!(check_tile(center, center, Some(true)).is_ok());
+        assert_eq!(check_tile(center, center, Some(false)), Err("на воде строить нельзя".to_string()));
+        assert_eq!(check_tile(center, center, None), Err("чанк не загружен".to_string()));
+    }
+}
@@ -0,0 +1,133 @@
+use bevy::prelude::*;
+use bevy::ecs::query::Has;
+use bevy::ecs::system::SystemParam;
+use bevy::window::PrimaryWindow;
+use bevy_prototype_lyon::prelude::*;
+use crate::game::collision::Collider;
+use crate::game::health::{DamageEvent, DamageSource, Health};
+
+const BULLET_RADIUS: f32 = 3.0;
+// Пули летят над всеми отсортированными спрайтами
+const BULLET_Z: f32 = 20.0;
+
+#[derive(Component)]
+pub struct Bullet {
+    // Кто выстрелил, в него пуля не попадает
+    pub owner: Entity,
+    pub velocity: Vec2,
+    pub damage: f32,
+    pub lifetime: Timer,
+}
+
+// Курсор в мировых координатах через камеру
+#[derive(SystemParam)]
+pub struct CursorPosition<'w, 's> {
+    window_query: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    camera_query: Query<'w, 's, (&'static Camera, &'static GlobalTransform)>,
+}
+
+impl CursorPosition<'_, '_> {
+    pub fn world_position(&self) -> Option<Vec2> {
+        let window = self.window_query.get_single().ok()?;
+        let (camera, camera_transform) = self.camera_query.get_single().ok()?;
+        camera.viewport_to_world_2d(camera_transform, window.cursor_position()?)
+    }
+}
+
+pub fn spawn_bullet(commands: &mut Commands, owner: Entity, position: Vec2, velocity: Vec2, damage: f32, lifetime: f32) {
+    let shape = shapes::Circle {
+        radius: BULLET_RADIUS,
+        center: Vec2::ZERO,
+    };
+
+    commands
+        .spawn((
+            ShapeBundle {
+                path: GeometryBuilder::build_as(&shape),
+                ..default()
+            },
+            Fill::color(Color::YELLOW),
+            Bullet {
+                owner,
+                velocity,
+                damage,
+                lifetime: Timer::from_seconds(lifetime, TimerMode::Once),
+            },
+        ))
+        .insert(Transform::from_xyz(position.x, position.y, BULLET_Z));
+}
+
+// Доля отрезка from..to, на которой круг задет впервые, или None, если отрезок его не задевает
+fn segment_hit(from: Vec2, to: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let segment = to - from;
+    let length_squared = segment.length_squared();
+    let t = if length_squared > 0.0 { ((center - from).dot(segment) / length_squared).clamp(0.0, 1.0) } else { 0.0 };
+    let distance = (from + segment * t).distance(center);
+    if distance >= radius {
+        return None;
+    }
+    // От ближайшей к центру точки отступаем назад до края круга
+    let back = if length_squared > 0.0 { (radius * radius - distance * distance).sqrt() / length_squared.sqrt() } else { 0.0 };
+    Some((t - back).max(0.0))
+}
+
+// Пули летят по прямой и исчезают по времени или попав в препятствие.
+// Попав в того, у кого есть здоровье, пуля наносит урон
+pub fn move_bullets(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut bullet_query: Query<(Entity, &mut Transform, &mut Bullet)>,
+    collider_query: Query<(Entity, &GlobalTransform, &Collider, Has<Health>)>,
+) {
+    for (entity, mut transform, mut bullet) in bullet_query.iter_mut() {
+        let from = transform.translation.truncate();
+        transform.translation += (bullet.velocity * time.delta_seconds()).extend(0.0);
+        let to = transform.translation.truncate();
+
+        // Проверяется весь путь за кадр, иначе при низком fps пуля пролетает препятствия насквозь.
+        // Попадает она в первое препятствие на пути
+        let hit = collider_query
+            .iter()
+            .filter(|(target, ..)| *target != bullet.owner)
+            .filter_map(|(target, collider_transform, collider, has_health)| {
+                let center = collider_transform.translation().truncate();
+                segment_hit(from, to, center, collider.radius + BULLET_RADIUS).map(|t| (t, target, has_health))
+            })
+            .min_by(|(a, ..), (b, ..)| a.total_cmp(b))
+            .map(|(_, target, has_health)| (target, has_health));
+
+        if let Some((target, true)) = hit {
+            damage_events.send(DamageEvent {
+                target,
+                amount: bullet.damage,
+                source: DamageSource::Projectile,
+            });
+        }
+        if bullet.lifetime.tick(time.delta()).finished() || hit.is_some() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // За один кадр пуля перелетает круг целиком, но всё равно в него попадает
+    #[test]
+    fn fast_bullet_hits_circle_it_passes() {
+        assert!(segment_hit(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0), Vec2::ZERO, 10.0).is_some());
+        assert!(segment_hit(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0), Vec2::new(0.0, 12.0), 10.0).is_none());
+        assert!(segment_hit(Vec2::new(-100.0, 0.0), Vec2::new(-50.0, 0.0), Vec2::ZERO, 10.0).is_none());
+    }
+
+    #[test]
+    fn nearer_circle_is_hit_first() {
+        let (from, to) = (Vec2::ZERO, Vec2::new(100.0, 0.0));
+        let wall = segment_hit(from, to, Vec2::new(30.0, 0.0), 10.0).unwrap();
+        let enemy = segment_hit(from, to, Vec2::new(60.0, 5.0), 10.0).unwrap();
+        assert!((wall - 0.2).abs() < 1e-4);
+        assert!(wall < enemy);
+    }
+}
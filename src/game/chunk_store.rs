@@ -0,0 +1,544 @@
+use bevy::prelude::Resource;
+use bevy::tasks::{IoTaskPool, Task};
+use futures_lite::future;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::game::biome::{BiomeRegistry, BiomeType};
+use crate::game::decoration::{DecorationRegistry, DecorationType};
+use crate::game::generate_map::{ChunkData, TileType};
+use crate::game::structure::{StructureRegistry, StructureType};
+use crate::game::world::ChunkPosition;
+
+// Один файл хранит квадрат REGION_SIZE x REGION_SIZE чанков
+const REGION_SIZE: i32 = 16;
+const MAGIC: &[u8; 4] = b"CHRG";
+// Файлы другой версии не читаются и не перезаписываются
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct RegionFile {
+    // Имена биомов и декораций по их индексам на момент сохранения,
+    // чтобы правки в assets не перепутали тайлы в старых сохранениях
+    biomes: Vec<String>,
+    decorations: Vec<String>,
+    structures: Vec<String>,
+    chunks: Vec<(ChunkPosition, ChunkData)>,
+}
+
+#[derive(Default)]
+struct Region {
+    chunks: HashMap<ChunkPosition, ChunkData>,
+    dirty: bool,
+    // Файл региона есть, но не прочитался. Такой регион никогда не пишется,
+    // иначе запись затёрла бы все сохранённые в нём чанки
+    read_only: bool,
+}
+
+impl Region {
+    fn loaded(chunks: RegionChunks) -> Self {
+        Self { chunks, dirty: false, read_only: false }
+    }
+
+    fn unreadable() -> Self {
+        Self { read_only: true, ..Self::default() }
+    }
+}
+
+type RegionChunks = HashMap<ChunkPosition, ChunkData>;
+// Ok(None): файла региона нет, все его чанки генерируются
+type RegionRead = Task<Result<Option<RegionChunks>, String>>;
+
+// Что хранилище знает о чанке
+pub enum StoredChunk {
+    // Чанк менялся, вот его сохранённое состояние
+    Found(ChunkData),
+    // Чанк не менялся, его нужно сгенерировать
+    Missing,
+    // Регион чанка ещё читается с диска
+    Loading,
+}
+
+// Имена биомов, декораций и построек по их индексам в реестрах. Пишутся в заголовок региона,
+// а при чтении по ним индексы из файла переводятся в текущие
+#[derive(Clone)]
+struct RegistryNames {
+    biomes: Vec<String>,
+    decorations: Vec<String>,
+    structures: Vec<String>,
+}
+
+impl RegistryNames {
+    fn new(biomes: &BiomeRegistry, decorations: &DecorationRegistry, structures: &StructureRegistry) -> Self {
+        Self {
+            biomes: biomes.iter().map(|biome| biome.name.clone()).collect(),
+            decorations: decorations.iter().map(|decoration| decoration.name.clone()).collect(),
+            structures: structures.iter().map(|structure| structure.name.clone()).collect(),
+        }
+    }
+}
+
+// Хранит только изменённые чанки, остальные всегда можно сгенерировать заново.
+// Выгруженные чанки пишутся в work_dir и попадают в каталог слота только в commit при сохранении игры,
+// иначе загрузка слота вернула бы игрока к сохранению, а мир оставила бы текущим.
+// Регионы читаются и пишутся в фоне, в памяти держатся только те, что нужны загруженным чанкам
+#[derive(Resource)]
+pub struct ChunkStore {
+    dir: PathBuf,
+    work_dir: PathBuf,
+    regions: HashMap<(i32, i32), Region>,
+    // Регионы, для которых на диске нет файла
+    missing: HashSet<(i32, i32)>,
+    // Регионы, которые сейчас читаются с диска в фоне
+    reads: HashMap<(i32, i32), RegionRead>,
+    // Регионы, которые сейчас пишутся на диск в фоне
+    writes: HashMap<(i32, i32), Task<Result<(), String>>>,
+}
+
+fn region_of(chunk_pos: ChunkPosition) -> (i32, i32) {
+    (chunk_pos.0.div_euclid(REGION_SIZE), chunk_pos.1.div_euclid(REGION_SIZE))
+}
+
+impl ChunkStore {
+    // Несохранённые чанки прошлого запуска выбрасываются: слот открывается в состоянии последнего сохранения
+    pub fn new(dir: PathBuf, work_dir: PathBuf) -> Self {
+        if work_dir.exists() {
+            if let Err(err) = fs::remove_dir_all(&work_dir) {
+                println!("Не удалось очистить {}: {}", work_dir.display(), err);
+            }
+        }
+
+        Self {
+            dir,
+            work_dir,
+            regions: HashMap::new(),
+            missing: HashSet::new(),
+            reads: HashMap::new(),
+            writes: HashMap::new(),
+        }
+    }
+
+    // Если регион чанка ещё не прочитан, чтение уходит в фон, а ответ придёт в одном из следующих кадров
+    pub fn lookup(
+        &mut self,
+        chunk_pos: ChunkPosition,
+        biomes: &BiomeRegistry,
+        decorations: &DecorationRegistry,
+        structures: &StructureRegistry,
+    ) -> StoredChunk {
+        let region_pos = region_of(chunk_pos);
+        if let Some(region) = self.regions.get(&region_pos) {
+            return region.chunks.get(&chunk_pos).cloned().map_or(StoredChunk::Missing, StoredChunk::Found);
+        }
+        if self.missing.contains(&region_pos) {
+            return StoredChunk::Missing;
+        }
+
+        if !self.reads.contains_key(&region_pos) {
+            let paths = self.region_paths(region_pos);
+            let names = RegistryNames::new(biomes, decorations, structures);
+            let task = IoTaskPool::get().spawn(async move { read_region(&paths, &names) });
+            self.reads.insert(region_pos, task);
+        }
+        StoredChunk::Loading
+    }
+
+    // Забирает законченные фоновые чтения и возвращает их ошибки.
+    // Чанки нечитаемого региона генерируются заново, но сам файл остаётся нетронутым
+    pub fn poll_reads(&mut self) -> Vec<String> {
+        let finished: Vec<_> = self
+            .reads
+            .iter_mut()
+            .filter_map(|(region_pos, task)| future::block_on(future::poll_once(task)).map(|result| (*region_pos, result)))
+            .collect();
+
+        let mut errors = Vec::new();
+        for (region_pos, result) in finished {
+            self.reads.remove(&region_pos);
+            match result {
+                Ok(Some(chunks)) => {
+                    self.regions.entry(region_pos).or_insert_with(|| Region::loaded(chunks));
+                }
+                Ok(None) => {
+                    self.missing.insert(region_pos);
+                }
+                Err(err) => {
+                    self.regions.entry(region_pos).or_insert_with(Region::unreadable);
+                    errors.push(err);
+                }
+            }
+        }
+        errors
+    }
+
+    pub fn insert(
+        &mut self,
+        chunk_pos: ChunkPosition,
+        chunk: ChunkData,
+        biomes: &BiomeRegistry,
+        decorations: &DecorationRegistry,
+        structures: &StructureRegistry,
+    ) {
+        let region = self.region(region_of(chunk_pos), biomes, decorations, structures);
+        region.chunks.insert(chunk_pos, chunk);
+        // Изменения в нечитаемом регионе живут только в памяти
+        region.dirty = !region.read_only;
+    }
+
+    // Забывает регионы, которые не нужны ни одному из чанков needed: без несохранённых изменений
+    // и без записи в фоне. Иначе за долгую игру в памяти оказался бы весь пройденный мир
+    pub fn evict(&mut self, needed: impl IntoIterator<Item = ChunkPosition>) {
+        let needed: HashSet<_> = needed.into_iter().map(region_of).collect();
+        let writes = &self.writes;
+        self.regions
+            .retain(|region_pos, region| region.dirty || writes.contains_key(region_pos) || needed.contains(region_pos));
+        self.missing.retain(|region_pos| needed.contains(region_pos));
+        // Брошенное чтение отменяется
+        self.reads.retain(|region_pos, _| needed.contains(region_pos));
+    }
+
+    // Отправляет в фон запись всех регионов, в которых что-то поменялось. Регион, который ещё
+    // пишется, остаётся грязным до следующего раза, чтобы две записи одного файла не обгоняли друг друга
+    pub fn flush(&mut self, biomes: &BiomeRegistry, decorations: &DecorationRegistry, structures: &StructureRegistry) {
+        let names = RegistryNames::new(biomes, decorations, structures);
+        let mut files = Vec::new();
+        for (&region_pos, region) in self.regions.iter_mut() {
+            if !region.dirty || self.writes.contains_key(&region_pos) {
+                continue;
+            }
+
+            let mut chunks: Vec<_> = region.chunks.iter().map(|(pos, chunk)| (*pos, chunk.clone())).collect();
+            chunks.sort_by_key(|(pos, _)| (pos.1, pos.0));
+            let file = RegionFile {
+                biomes: names.biomes.clone(),
+                decorations: names.decorations.clone(),
+                structures: names.structures.clone(),
+                chunks,
+            };
+            files.push((region_pos, file));
+            region.dirty = false;
+        }
+
+        let pool = IoTaskPool::get();
+        for (region_pos, file) in files {
+            let path = region_path(&self.work_dir, region_pos);
+            let task = pool.spawn(async move { write_region(&path, &file) });
+            self.writes.insert(region_pos, task);
+        }
+    }
+
+    // Забирает законченные фоновые записи и возвращает их ошибки.
+    // Регион, который не удалось записать, снова помечается грязным
+    pub fn poll_writes(&mut self) -> Vec<String> {
+        let finished: Vec<_> = self
+            .writes
+            .iter_mut()
+            .filter_map(|(region_pos, task)| future::block_on(future::poll_once(task)).map(|result| (*region_pos, result)))
+            .collect();
+
+        let mut errors = Vec::new();
+        for (region_pos, result) in finished {
+            self.writes.remove(&region_pos);
+            if let Err(err) = result {
+                self.mark_dirty(region_pos);
+                errors.push(err);
+            }
+        }
+        errors
+    }
+
+    // Дожидается всех фоновых записей
+    pub fn wait(&mut self) -> Result<(), String> {
+        let mut result = Ok(());
+        for (region_pos, task) in std::mem::take(&mut self.writes) {
+            if let Err(err) = future::block_on(task) {
+                self.mark_dirty(region_pos);
+                result = Err(err);
+            }
+        }
+        result
+    }
+
+    // Пишет всё прямо сейчас и возвращается, только когда файлы уже на диске
+    pub fn flush_now(
+        &mut self,
+        biomes: &BiomeRegistry,
+        decorations: &DecorationRegistry,
+        structures: &StructureRegistry,
+    ) -> Result<(), String> {
+        let waited = self.wait();
+        self.flush(biomes, decorations, structures);
+        self.wait().and(waited)
+    }
+
+    // Переносит все несохранённые регионы в каталог слота
+    pub fn commit(
+        &mut self,
+        biomes: &BiomeRegistry,
+        decorations: &DecorationRegistry,
+        structures: &StructureRegistry,
+    ) -> Result<(), String> {
+        self.flush_now(biomes, decorations, structures)?;
+        let Ok(entries) = fs::read_dir(&self.work_dir) else {
+            return Ok(());
+        };
+        fs::create_dir_all(&self.dir).map_err(|err| format!("{}: {}", self.dir.display(), err))?;
+
+        for entry in entries {
+            let path = entry.map_err(|err| err.to_string())?.path();
+            let (Some(name), Some("bin")) = (path.file_name(), path.extension().and_then(|ext| ext.to_str())) else {
+                continue;
+            };
+            // Оба каталога лежат в слоте, так что переименование заменяет регион целиком
+            let target = self.dir.join(name);
+            fs::rename(&path, &target).map_err(|err| format!("{}: {}", target.display(), err))?;
+        }
+        Ok(())
+    }
+
+    fn mark_dirty(&mut self, region_pos: (i32, i32)) {
+        if let Some(region) = self.regions.get_mut(&region_pos) {
+            region.dirty = true;
+        }
+    }
+
+    // Забывает прочитанные регионы, например после перезагрузки реестров.
+    // Несохранённые изменения тоже теряются, поэтому сначала нужен flush_now
+    pub fn clear_cache(&mut self) {
+        self.regions.clear();
+        self.missing.clear();
+        self.reads.clear();
+    }
+
+    // Несохранённая версия региона новее сохранённой
+    fn region_paths(&self, region_pos: (i32, i32)) -> [PathBuf; 2] {
+        [region_path(&self.work_dir, region_pos), region_path(&self.dir, region_pos)]
+    }
+
+    // Запись не ждёт фонового чтения: регион, который ещё не в памяти, читается сразу
+    fn region(
+        &mut self,
+        region_pos: (i32, i32),
+        biomes: &BiomeRegistry,
+        decorations: &DecorationRegistry,
+        structures: &StructureRegistry,
+    ) -> &mut Region {
+        self.reads.remove(&region_pos);
+        let missing = self.missing.remove(&region_pos);
+        let paths = self.region_paths(region_pos);
+        self.regions.entry(region_pos).or_insert_with(|| {
+            if missing {
+                return Region::default();
+            }
+            match read_region(&paths, &RegistryNames::new(biomes, decorations, structures)) {
+                Ok(chunks) => Region::loaded(chunks.unwrap_or_default()),
+                Err(err) => {
+                    println!("Не удалось прочитать регион: {}", err);
+                    Region::unreadable()
+                }
+            }
+        })
+    }
+}
+
+// Брошенная задача отменяется, поэтому хранилище не уходит, пока не допишет регионы
+impl Drop for ChunkStore {
+    fn drop(&mut self) {
+        if let Err(err) = self.wait() {
+            println!("Ошибка сохранения чанков: {}", err);
+        }
+    }
+}
+
+fn write_region(path: &Path, file: &RegionFile) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    }
+
+    let bytes = encode_region(file)?;
+
+    // Пишем во временный файл и переименовываем, чтобы не оставить полузаписанный регион
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, bytes).map_err(|err| format!("{}: {}", temp.display(), err))?;
+    fs::rename(&temp, path).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+fn region_path(dir: &Path, region_pos: (i32, i32)) -> PathBuf {
+    dir.join(format!("r.{}.{}.bin", region_pos.0, region_pos.1))
+}
+
+fn read_region(paths: &[PathBuf], names: &RegistryNames) -> Result<Option<RegionChunks>, String> {
+    let Some(path) = paths.iter().find(|path| path.exists()) else {
+        return Ok(None);
+    };
+    let bytes = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let file = decode_region(&bytes).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    // Реестры отсортированы по имени, так что и списки имён тоже
+    let position = |names: &[String], name: &String| names.binary_search(name).ok();
+    // Индексы из файла переводим в индексы текущих реестров
+    let biome_map: Vec<BiomeType> = file
+        .biomes
+        .iter()
+        .map(|name| {
+            position(&names.biomes, name).map(|index| BiomeType(index as u16)).unwrap_or_else(|| {
+                println!("Регион {}: неизвестный биом {}", path.display(), name);
+                BiomeType(0)
+            })
+        })
+        .collect();
+    let decoration_map: Vec<Option<DecorationType>> = file
+        .decorations
+        .iter()
+        .map(|name| position(&names.decorations, name).map(|index| DecorationType(index as u16)))
+        .collect();
+    let structure_map: Vec<Option<StructureType>> = file
+        .structures
+        .iter()
+        .map(|name| position(&names.structures, name).map(|index| StructureType(index as u16)))
+        .collect();
+    let biome = |biome: BiomeType| biome_map.get(biome.0 as usize).copied().unwrap_or(BiomeType(0));
+
+    let mut chunks = HashMap::new();
+    for (chunk_pos, mut chunk) in file.chunks {
+        for tile in &mut chunk.tiles {
+            tile.biome = biome(tile.biome);
+            if let TileType::BiomeBorder { from, to, rotation } = tile.tile_type {
+                tile.tile_type = TileType::BiomeBorder { from: biome(from), to: biome(to), rotation };
+            }
+        }
+        // Декорации, которых больше нет в assets, пропадают
+        chunk.decorations.retain_mut(|decoration| {
+            match decoration_map.get(decoration.kind.0 as usize).copied().flatten() {
+                Some(kind) => {
+                    decoration.kind = kind;
+                    true
+                }
+                None => false,
+            }
+        });
+        // Постройки тоже
+        chunk.structures.retain_mut(|structure| {
+            match structure_map.get(structure.kind.0 as usize).copied().flatten() {
+                Some(kind) => {
+                    structure.kind = kind;
+                    true
+                }
+                None => false,
+            }
+        });
+        chunks.insert(chunk_pos, chunk);
+    }
+
+    Ok(Some(chunks))
+}
+
+fn encode_region(file: &RegionFile) -> Result<Vec<u8>, String> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(FORMAT_VERSION.to_le_bytes());
+    bytes.extend(bincode::serialize(file).map_err(|err| err.to_string())?);
+    Ok(bytes)
+}
+
+fn decode_region(bytes: &[u8]) -> Result<RegionFile, String> {
+    if bytes.len() < 8 || &bytes[..4] != MAGIC {
+        return Err("это не файл региона".to_string());
+    }
+    match u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) {
+        FORMAT_VERSION => bincode::deserialize(&bytes[8..]).map_err(|err| err.to_string()),
+        version => Err(format!("неподдерживаемая версия формата {}", version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::tasks::TaskPool;
+    use super::*;
+    use crate::game::data::assets_dir;
+    use crate::game::generate_map::{generate_chunk, DroppedItem, PlacedStation, Structure};
+    use crate::game::terrain::{TerrainGenerator, TerrainSettings};
+    use crate::game::world::TilePos;
+
+    fn registries() -> (BiomeRegistry, DecorationRegistry, StructureRegistry) {
+        let decorations = DecorationRegistry::load(&assets_dir().join("decorations")).unwrap();
+        let biomes = BiomeRegistry::load(&assets_dir().join("biomes"), &decorations).unwrap();
+        let structures = StructureRegistry::load(&assets_dir().join("structures")).unwrap();
+        (biomes, decorations, structures)
+    }
+
+    // Каталог слота для теста, пустой к началу теста
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chunk_store_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn region_file() -> RegionFile {
+        let (biomes, decorations, structures) = registries();
+        let names = RegistryNames::new(&biomes, &decorations, &structures);
+        let generator = TerrainGenerator::new(7, TerrainSettings::default());
+        let chunk_pos = ChunkPosition(-1, 2);
+        let mut chunk = generate_chunk(&generator, &biomes, chunk_pos);
+        chunk.structures.push(Structure { kind: StructureType(0), position: chunk_pos.tile(3, 4), health: 12.5 });
+        chunk.items.push(DroppedItem { item: "wood".to_string(), count: 3, position: (-10.25, 40.5) });
+        chunk.stations.push(PlacedStation { station: "workbench".to_string(), position: TilePos(-9, 33) });
+
+        RegionFile {
+            biomes: names.biomes,
+            decorations: names.decorations,
+            structures: names.structures,
+            chunks: vec![(chunk_pos, chunk)],
+        }
+    }
+
+    #[test]
+    fn region_round_trip() {
+        let file = region_file();
+        let decoded = decode_region(&encode_region(&file).unwrap()).unwrap();
+
+        assert_eq!(decoded.biomes, file.biomes);
+        assert_eq!(decoded.decorations, file.decorations);
+        assert_eq!(decoded.structures, file.structures);
+        assert_eq!(decoded.chunks, file.chunks);
+    }
+
+    #[test]
+    fn other_version_is_rejected() {
+        let mut bytes = encode_region(&region_file()).unwrap();
+        bytes[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(decode_region(&bytes).is_err());
+        assert!(decode_region(b"CHR").is_err());
+    }
+
+    // Регион, который не прочитался, не должен затираться при сохранении игры
+    #[test]
+    fn unreadable_region_is_not_overwritten() {
+        IoTaskPool::init(TaskPool::new);
+        let (biomes, decorations, structures) = registries();
+        let dir = test_dir("unreadable");
+        let chunk_pos = ChunkPosition(1, 1);
+
+        let mut bytes = encode_region(&region_file()).unwrap();
+        bytes[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        let path = region_path(&dir.join("chunks"), region_of(chunk_pos));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &bytes).unwrap();
+
+        let mut store = ChunkStore::new(dir.join("chunks"), dir.join("unsaved"));
+        assert!(matches!(store.lookup(chunk_pos, &biomes, &decorations, &structures), StoredChunk::Loading));
+        let mut errors = Vec::new();
+        while errors.is_empty() {
+            errors = store.poll_reads();
+        }
+        assert!(matches!(store.lookup(chunk_pos, &biomes, &decorations, &structures), StoredChunk::Missing));
+
+        let chunk = region_file().chunks.remove(0).1;
+        store.insert(chunk_pos, chunk, &biomes, &decorations, &structures);
+        store.commit(&biomes, &decorations, &structures).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+
+        drop(store);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
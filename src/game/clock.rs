@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+
+// Длина суток в секундах
+const DAY_LENGTH: f64 = 600.0;
+// Доля суток, которую занимает день. Игра начинается с утра
+const DAY_SHARE: f64 = 0.65;
+
+// Игровое время мира в секундах. Идёт только вне паузы и хранится в сохранении,
+// поэтому по нему можно отсчитывать события, пережившие выгрузку чанка
+#[derive(Resource, Default)]
+pub struct WorldClock {
+    pub elapsed: f64,
+}
+
+impl WorldClock {
+    // Время суток от 0 до 1, с нуля начинается утро
+    pub fn time_of_day(&self) -> f64 {
+        (self.elapsed / DAY_LENGTH).fract()
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.time_of_day() >= DAY_SHARE
+    }
+}
+
+pub fn tick_clock(time: Res<Time>, mut clock: ResMut<WorldClock>) {
+    clock.elapsed += time.delta_seconds_f64();
+}
@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+use crate::game::tile_query::TileQuery;
+use crate::game::world::TilePos;
+
+// Круглое препятствие, радиус в мировых единицах
+#[derive(Component)]
+pub struct Collider {
+    pub radius: f32,
+}
+
+// Тайлы под квадратом, описанным вокруг круга
+fn tiles_under(position: Vec2, radius: f32) -> impl Iterator<Item = TilePos> {
+    let min = TilePos::from_world(position - Vec2::splat(radius));
+    let max = TilePos::from_world(position + Vec2::splat(radius));
+    (min.1..=max.1).flat_map(move |y| (min.0..=max.0).map(move |x| TilePos(x, y)))
+}
+
+// Сдвигает круг на delta. Упираясь в непроходимый тайл, круг скользит вдоль оси,
+// а из круглых препятствий выталкивается по нормали, поэтому обходит их по дуге
+pub fn move_and_slide<'a>(
+    tiles: &TileQuery,
+    colliders: impl Iterator<Item = (Vec2, &'a Collider)>,
+    from: Vec2,
+    delta: Vec2,
+    radius: f32,
+) -> Vec2 {
+    let walkable = |tile: TilePos| tiles.tile(tile).map(|info| info.walkable);
+    slide(walkable, colliders, from, delta, radius)
+}
+
+// walkable отвечает None для тайлов незагруженных чанков
+fn slide<'a>(
+    walkable: impl Fn(TilePos) -> Option<bool>,
+    colliders: impl Iterator<Item = (Vec2, &'a Collider)>,
+    from: Vec2,
+    delta: Vec2,
+    radius: f32,
+) -> Vec2 {
+    // Если уже стоим на загруженном непроходимом тайле, с него можно сойти, но не на другой непроходимый.
+    // Незагруженные тайлы непроходимы всегда
+    let stuck: Vec<TilePos> = tiles_under(from, radius)
+        .filter(|tile| walkable(*tile) == Some(false))
+        .collect();
+    let free = |position: Vec2| {
+        tiles_under(position, radius).all(|tile| walkable(tile) == Some(true) || stuck.contains(&tile))
+    };
+
+    let mut position = from;
+    for step in [Vec2::new(delta.x, 0.0), Vec2::new(0.0, delta.y)] {
+        if step != Vec2::ZERO && free(position + step) {
+            position += step;
+        }
+    }
+
+    for (center, collider) in colliders {
+        let offset = position - center;
+        let min_distance = radius + collider.radius;
+        let distance = offset.length();
+        if distance < min_distance {
+            let normal = if distance > f32::EPSILON { offset / distance } else { Vec2::Y };
+            position = center + normal * min_distance;
+        }
+    }
+
+    if position != from && !free(position) {
+        return from;
+    }
+    position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::TILE_SIZE;
+
+    const RADIUS: f32 = 10.0;
+
+    // Загруженный квадрат тайлов от -5 до 5, непроходимые тайлы перечислены в blocked
+    fn walkable(blocked: &[TilePos]) -> impl Fn(TilePos) -> Option<bool> + '_ {
+        |tile: TilePos| {
+            let loaded = (-5..=5).contains(&tile.0) && (-5..=5).contains(&tile.1);
+            loaded.then(|| !blocked.contains(&tile))
+        }
+    }
+
+    #[test]
+    fn slides_along_blocked_axis() {
+        let wall: Vec<TilePos> = (-5..=5).map(|y| TilePos(2, y)).collect();
+        let from = TilePos(1, 0).to_world();
+
+        let moved = slide(walkable(&wall), std::iter::empty(), from, Vec2::new(20.0, 20.0), RADIUS);
+        assert_eq!(moved, from + Vec2::new(0.0, 20.0), "вдоль стены движение должно сохраниться");
+    }
+
+    #[test]
+    fn pushed_out_of_collider() {
+        let collider = Collider { radius: 10.0 };
+        let colliders = [(Vec2::ZERO, &collider)];
+
+        let moved = slide(walkable(&[]), colliders.into_iter(), Vec2::new(25.0, 0.0), Vec2::new(-10.0, 0.0), RADIUS);
+        assert!((moved.length() - (RADIUS + collider.radius)).abs() < 1e-4, "{:?}", moved);
+        assert!(moved.x > 0.0, "выталкивать нужно в сторону, откуда пришли: {:?}", moved);
+    }
+
+    #[test]
+    fn steps_off_own_blocked_tile() {
+        let blocked = [TilePos(0, 0), TilePos(-1, 0)];
+
+        let moved = slide(walkable(&blocked), std::iter::empty(), Vec2::ZERO, Vec2::new(20.0, 0.0), RADIUS);
+        assert_eq!(moved, Vec2::new(20.0, 0.0), "со своего непроходимого тайла можно сойти");
+
+        let moved = slide(walkable(&blocked), std::iter::empty(), Vec2::ZERO, Vec2::new(-20.0, 0.0), RADIUS);
+        assert_eq!(moved, Vec2::ZERO, "на соседний непроходимый тайл заходить нельзя");
+    }
+
+    #[test]
+    fn unloaded_tiles_block() {
+        let from = TilePos(5, 0).to_world();
+
+        let moved = slide(walkable(&[]), std::iter::empty(), from, Vec2::new(TILE_SIZE, 0.0), RADIUS);
+        assert_eq!(moved, from, "в незагруженный чанк заходить нельзя");
+    }
+}
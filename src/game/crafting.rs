@@ -0,0 +1,466 @@
+use std::path::Path;
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::game::data::{assets_dir, load_ron_dir};
+use crate::game::health::Dead;
+use crate::game::inventory::{spawn_world_item, Inventory, IMMEDIATE_PICKUP};
+use crate::game::inventory_ui::{InventoryUi, InventoryWindow};
+use crate::game::item::{ItemRegistry, ItemStack};
+use crate::game::player::Player;
+use crate::game::station::{nearby_stations, CraftingStation, StationRegistry, StationType};
+
+const RECIPE_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+const CRAFTABLE_COLOR: Color = Color::rgb(0.2, 0.4, 0.2);
+const CRAFTABLE_HOVER_COLOR: Color = Color::rgb(0.3, 0.55, 0.3);
+
+// Индекс рецепта в реестре, рецепты сортируются по имени
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecipeType(pub u16);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecipeDefinition {
+    pub name: String,
+    // Имя предмета и количество
+    pub inputs: Vec<(String, u32)>,
+    pub outputs: Vec<(String, u32)>,
+    // Время изготовления в секундах
+    pub craft_time: f32,
+    // Станция, рядом с которой нужно стоять. Без неё рецепт делается прямо из инвентаря
+    #[serde(default)]
+    pub station: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Recipe {
+    // Название первого результата для интерфейса
+    pub title: String,
+    pub inputs: Vec<ItemStack>,
+    pub outputs: Vec<ItemStack>,
+    pub craft_time: f32,
+    pub station: Option<StationType>,
+}
+
+impl Recipe {
+    // Один предмет может встретиться во входах несколько раз, тогда нужна сумма
+    pub fn has_materials(&self, inventory: &Inventory) -> bool {
+        self.inputs.iter().all(|input| {
+            let required: u32 = self.inputs.iter().filter(|other| other.item == input.item).map(|other| other.count).sum();
+            inventory.count(input.item) >= required
+        })
+    }
+
+    // Списывает все материалы рецепта или, если чего-то не хватает, ничего
+    pub fn consume(&self, inventory: &mut Inventory) -> bool {
+        if !self.has_materials(inventory) {
+            return false;
+        }
+        for input in &self.inputs {
+            inventory.remove(input.item, input.count);
+        }
+        true
+    }
+
+    pub fn can_craft(&self, inventory: &Inventory, stations: &[StationType]) -> bool {
+        self.station.is_none_or(|station| stations.contains(&station)) && self.has_materials(inventory)
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct RecipeRegistry {
+    recipes: Vec<Recipe>,
+}
+
+impl RecipeRegistry {
+    pub fn load(dir: &Path, items: &ItemRegistry, stations: &StationRegistry) -> Result<Self, String> {
+        Self::from_definitions(load_ron_dir(dir)?, items, stations)
+    }
+
+    pub fn from_definitions(
+        mut definitions: Vec<RecipeDefinition>,
+        items: &ItemRegistry,
+        stations: &StationRegistry,
+    ) -> Result<Self, String> {
+        if definitions.len() > u16::MAX as usize {
+            return Err(format!("слишком много рецептов: {}", definitions.len()));
+        }
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+
+        if let Some(pair) = definitions.windows(2).find(|pair| pair[0].name == pair[1].name) {
+            return Err(format!("рецепт {} объявлен дважды", pair[0].name));
+        }
+
+        let mut recipes = Vec::with_capacity(definitions.len());
+        for definition in definitions {
+            let resolve = |stacks: &[(String, u32)]| {
+                stacks
+                    .iter()
+                    .map(|(name, count)| {
+                        let item = items
+                            .find(name)
+                            .ok_or_else(|| format!("рецепт {}: неизвестный предмет {}", definition.name, name))?;
+                        Ok(ItemStack { item, count: *count })
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+            };
+            let inputs = resolve(&definition.inputs)?;
+            let outputs = resolve(&definition.outputs)?;
+
+            let Some(first) = outputs.first() else {
+                return Err(format!("рецепт {}: нет результата", definition.name));
+            };
+            if definition.craft_time < 0.0 {
+                return Err(format!("рецепт {}: отрицательное время изготовления", definition.name));
+            }
+            let station = match &definition.station {
+                Some(name) => Some(
+                    stations
+                        .find(name)
+                        .ok_or_else(|| format!("рецепт {}: неизвестная станция {}", definition.name, name))?,
+                ),
+                None => None,
+            };
+
+            recipes.push(Recipe {
+                title: items.get(first.item).title.clone(),
+                inputs,
+                outputs,
+                craft_time: definition.craft_time,
+                station,
+            });
+        }
+
+        Ok(Self { recipes })
+    }
+
+    pub fn get(&self, recipe: RecipeType) -> &Recipe {
+        &self.recipes[recipe.0 as usize]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (RecipeType, &Recipe)> {
+        self.recipes
+            .iter()
+            .enumerate()
+            .map(|(index, recipe)| (RecipeType(index as u16), recipe))
+    }
+}
+
+// Что игрок сейчас мастерит. Материалы списываются в начале, результат выдаётся по таймеру
+#[derive(Component, Default)]
+pub struct Crafter {
+    current: Option<(RecipeType, Timer)>,
+}
+
+impl Crafter {
+    pub fn progress(&self) -> Option<(RecipeType, f32)> {
+        self.current.as_ref().map(|(recipe, timer)| (*recipe, timer.percent()))
+    }
+}
+
+#[derive(Component)]
+pub struct RecipeButton(RecipeType);
+
+#[derive(Component)]
+pub struct CraftingProgress;
+
+// Реестры станций и рецептов ссылаются на предметы, поэтому грузятся после них
+pub fn setup_crafting(
+    mut commands: Commands,
+    items: Res<ItemRegistry>,
+    window_query: Query<Entity, With<InventoryWindow>>,
+) {
+    let stations = StationRegistry::load(&assets_dir().join("stations"), &items)
+        .unwrap_or_else(|err| panic!("Не удалось загрузить станции: {}", err));
+    let recipes = RecipeRegistry::load(&assets_dir().join("recipes"), &items, &stations)
+        .unwrap_or_else(|err| panic!("Не удалось загрузить рецепты: {}", err));
+
+    // Список рецептов показывается в окне инвентаря рядом со слотами
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    commands.entity(window).with_children(|parent| {
+        parent
+            .spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::all(Val::Px(12.0)),
+                    margin: UiRect::left(Val::Px(12.0)),
+                    row_gap: Val::Px(6.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.1, 0.1, 0.1, 0.9).into(),
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Крафт",
+                    TextStyle {
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+                parent.spawn((
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 18.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    CraftingProgress,
+                ));
+
+                for (kind, recipe) in recipes.iter() {
+                    let inputs: Vec<String> = recipe
+                        .inputs
+                        .iter()
+                        .map(|input| format!("{} {}", items.get(input.item).title, input.count))
+                        .collect();
+                    let mut text = format!("{} x{}: {}", recipe.title, recipe.outputs[0].count, inputs.join(", "));
+                    if let Some(station) = recipe.station {
+                        text.push_str(&format!(" [{}]", stations.get(station).title));
+                    }
+
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(360.0),
+                                    padding: UiRect::all(Val::Px(6.0)),
+                                    ..default()
+                                },
+                                background_color: RECIPE_COLOR.into(),
+                                ..default()
+                            },
+                            RecipeButton(kind),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(
+                                text,
+                                TextStyle {
+                                    font_size: 16.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                },
+                            ));
+                        });
+                }
+            });
+    });
+
+    commands.insert_resource(stations);
+    commands.insert_resource(recipes);
+}
+
+pub fn craft_buttons(
+    interaction_query: Query<(&Interaction, &RecipeButton), Changed<Interaction>>,
+    recipes: Res<RecipeRegistry>,
+    stations: Res<StationRegistry>,
+    station_query: Query<(&GlobalTransform, &CraftingStation)>,
+    mut player_query: Query<(&Transform, &mut Inventory, &mut Crafter), (With<Player>, Without<Dead>)>,
+) {
+    let Ok((transform, mut inventory, mut crafter)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction != Interaction::Pressed || crafter.current.is_some() {
+            continue;
+        }
+        let recipe = recipes.get(button.0);
+        let nearby = nearby_stations(&stations, station_query.iter(), transform.translation.truncate());
+        if !recipe.can_craft(&inventory, &nearby) || !recipe.consume(&mut inventory) {
+            continue;
+        }
+        crafter.current = Some((button.0, Timer::from_seconds(recipe.craft_time, TimerMode::Once)));
+    }
+}
+
+pub fn tick_crafting(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    items: Res<ItemRegistry>,
+    recipes: Res<RecipeRegistry>,
+    mut player_query: Query<(&Transform, &mut Inventory, &mut Crafter), (With<Player>, Without<Dead>)>,
+) {
+    let Ok((transform, mut inventory, mut crafter)) = player_query.get_single_mut() else {
+        return;
+    };
+    let Some((recipe, timer)) = crafter.current.as_mut() else {
+        return;
+    };
+    if !timer.tick(time.delta()).finished() {
+        return;
+    }
+
+    let recipe = recipes.get(*recipe);
+    for output in &recipe.outputs {
+        let left = inventory.add(&items, *output);
+        // То, что не влезло в инвентарь, падает на землю
+        if left > 0 {
+            let stack = ItemStack { item: output.item, count: left };
+            spawn_world_item(
+                &mut commands,
+                &asset_server,
+                &items,
+                stack,
+                transform.translation.truncate(),
+                IMMEDIATE_PICKUP,
+            );
+        }
+    }
+    crafter.current = None;
+}
+
+// Доступные рецепты подсвечиваются, пока открыт инвентарь
+pub fn update_crafting_ui(
+    ui: Res<InventoryUi>,
+    recipes: Res<RecipeRegistry>,
+    stations: Res<StationRegistry>,
+    station_query: Query<(&GlobalTransform, &CraftingStation)>,
+    player_query: Query<(&Transform, &Inventory, &Crafter), With<Player>>,
+    mut button_query: Query<(&RecipeButton, &Interaction, &mut BackgroundColor)>,
+    mut progress_query: Query<&mut Text, With<CraftingProgress>>,
+) {
+    if !ui.open {
+        return;
+    }
+    let Ok((transform, inventory, crafter)) = player_query.get_single() else {
+        return;
+    };
+    let nearby = nearby_stations(&stations, station_query.iter(), transform.translation.truncate());
+
+    for (button, interaction, mut background) in button_query.iter_mut() {
+        let craftable = crafter.current.is_none() && recipes.get(button.0).can_craft(inventory, &nearby);
+        let color = match (craftable, interaction) {
+            (false, _) => RECIPE_COLOR,
+            (true, Interaction::None) => CRAFTABLE_COLOR,
+            (true, _) => CRAFTABLE_HOVER_COLOR,
+        };
+        if background.0 != color {
+            *background = color.into();
+        }
+    }
+
+    for mut text in progress_query.iter_mut() {
+        text.sections[0].value = match crafter.progress() {
+            Some((recipe, progress)) => format!("{}: {:.0}%", recipes.get(recipe).title, progress * 100.0),
+            None => String::new(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::item::test_item;
+    use crate::game::station::StationDefinition;
+
+    fn items() -> ItemRegistry {
+        let item = |name| test_item(name, 20, 0.0);
+        ItemRegistry::from_definitions(vec![item("wood"), item("stone"), item("pickaxe"), item("workbench")]).unwrap()
+    }
+
+    fn stations(items: &ItemRegistry) -> StationRegistry {
+        let workbench = StationDefinition {
+            name: "workbench".to_string(),
+            title: "workbench".to_string(),
+            item: "workbench".to_string(),
+            texture: String::new(),
+            range: 2.0,
+            collision_radius: None,
+            warmth: None,
+            bed: false,
+            item_type: Default::default(),
+        };
+        StationRegistry::from_definitions(vec![workbench], items).unwrap()
+    }
+
+    fn recipe(inputs: &[(&str, u32)], outputs: &[(&str, u32)], station: Option<&str>) -> RecipeDefinition {
+        let stacks = |stacks: &[(&str, u32)]| stacks.iter().map(|(name, count)| (name.to_string(), *count)).collect();
+        RecipeDefinition {
+            name: "recipe".to_string(),
+            inputs: stacks(inputs),
+            outputs: stacks(outputs),
+            craft_time: 1.0,
+            station: station.map(str::to_string),
+        }
+    }
+
+    fn load(definition: RecipeDefinition) -> Result<RecipeRegistry, String> {
+        let items = items();
+        let stations = stations(&items);
+        RecipeRegistry::from_definitions(vec![definition], &items, &stations)
+    }
+
+    #[test]
+    fn unknown_item_is_rejected() {
+        let err = load(recipe(&[("iron", 1)], &[("pickaxe", 1)], None)).unwrap_err();
+        assert!(err.contains("iron"), "{}", err);
+        let err = load(recipe(&[("wood", 1)], &[("sword", 1)], None)).unwrap_err();
+        assert!(err.contains("sword"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_station_is_rejected() {
+        let err = load(recipe(&[("wood", 1)], &[("pickaxe", 1)], Some("anvil"))).unwrap_err();
+        assert!(err.contains("anvil"), "{}", err);
+        assert!(load(recipe(&[("wood", 1)], &[("pickaxe", 1)], Some("workbench"))).is_ok());
+    }
+
+    #[test]
+    fn recipe_without_outputs_is_rejected() {
+        assert!(load(recipe(&[("wood", 1)], &[], None)).is_err());
+    }
+
+    #[test]
+    fn station_is_required_nearby() {
+        let items = items();
+        let stations = stations(&items);
+        let registry =
+            RecipeRegistry::from_definitions(vec![recipe(&[("wood", 1)], &[("pickaxe", 1)], Some("workbench"))], &items, &stations)
+                .unwrap();
+        let recipe = registry.get(RecipeType(0));
+        let mut inventory = Inventory::new(4, 100.0);
+        inventory.add(&items, ItemStack { item: items.find("wood").unwrap(), count: 1 });
+
+        assert!(!recipe.can_craft(&inventory, &[]));
+        assert!(recipe.can_craft(&inventory, &[stations.find("workbench").unwrap()]));
+    }
+
+    #[test]
+    fn consume_takes_all_inputs_or_nothing() {
+        let items = items();
+        let registry = load(recipe(&[("wood", 3), ("stone", 2)], &[("pickaxe", 1)], None)).unwrap();
+        let recipe = registry.get(RecipeType(0));
+        let wood = items.find("wood").unwrap();
+        let stone = items.find("stone").unwrap();
+
+        let mut inventory = Inventory::new(4, 100.0);
+        inventory.add(&items, ItemStack { item: wood, count: 5 });
+        inventory.add(&items, ItemStack { item: stone, count: 1 });
+        assert!(!recipe.consume(&mut inventory));
+        assert_eq!((inventory.count(wood), inventory.count(stone)), (5, 1));
+
+        inventory.add(&items, ItemStack { item: stone, count: 1 });
+        assert!(recipe.consume(&mut inventory));
+        assert_eq!((inventory.count(wood), inventory.count(stone)), (2, 0));
+    }
+
+    // Повторяющийся вход учитывается суммой, а не по отдельности
+    #[test]
+    fn repeated_input_needs_the_sum() {
+        let items = items();
+        let registry = load(recipe(&[("wood", 2), ("wood", 2)], &[("pickaxe", 1)], None)).unwrap();
+        let recipe = registry.get(RecipeType(0));
+        let wood = items.find("wood").unwrap();
+
+        let mut inventory = Inventory::new(4, 100.0);
+        inventory.add(&items, ItemStack { item: wood, count: 3 });
+        assert!(!recipe.consume(&mut inventory));
+        assert_eq!(inventory.count(wood), 3);
+    }
+}
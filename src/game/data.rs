@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use bevy::asset::FileAssetIo;
+use serde::de::DeserializeOwned;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Тот же каталог assets, из которого грузит текстуры AssetServer
+pub fn assets_dir() -> PathBuf {
+    FileAssetIo::get_base_path().join("assets")
+}
+
+// Сохранения лежат рядом с assets
+pub fn saves_dir() -> PathBuf {
+    FileAssetIo::get_base_path().join("saves")
+}
+
+// Загружает все .ron файлы каталога в порядке имён файлов
+pub fn load_ron_dir<T: DeserializeOwned>(dir: &Path) -> Result<Vec<T>, String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .collect();
+    paths.sort();
+
+    paths.iter().map(|path| load_ron(path)).collect()
+}
+
+pub fn load_ron<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    ron::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+// Следит за файлами каталога для горячей перезагрузки данных
+pub struct DirWatcher {
+    dir: PathBuf,
+    last_modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl DirWatcher {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            last_modified: latest_modification(&dir),
+            last_check: Instant::now(),
+            dir,
+        }
+    }
+
+    // Возвращает true, если с прошлой проверки что-то поменялось
+    pub fn poll(&mut self) -> bool {
+        if self.last_check.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+
+        let modified = latest_modification(&self.dir);
+        if modified != self.last_modified {
+            self.last_modified = modified;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn latest_modification(dir: &Path) -> Option<SystemTime> {
+    // Время изменения самого каталога учитывает добавление и удаление файлов
+    let dir_modified = fs::metadata(dir).and_then(|meta| meta.modified()).ok();
+
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok()?.metadata().ok()?.modified().ok())
+        .chain(dir_modified)
+        .max()
+}
@@ -1,28 +1,134 @@
 use bevy::prelude::*;
-use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::ecs::query::Has;
+use bevy::app::AppExit;
+use bevy::diagnostic::{DiagnosticsStore, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin};
+use crate::game::bullet::CursorPosition;
+use crate::game::enemy::{EnemySpawner, EnemyType};
+use crate::game::map::{ChunkPersistence, MapState};
+use crate::game::player::Player;
+use crate::game::tile_query::TileQuery;
+use crate::game::tilemap::{ChunkTiles, TileRenderMode, TileSprite};
+use crate::game::world::TilePos;
 
-#[derive(Debug, Resource)]
+// Мир замера всегда один и тот же
+const BENCHMARK_SEED: u64 = 1;
+// Сколько секунд ждать, пока загрузятся чанки вокруг игрока
+const BENCHMARK_WARMUP: f32 = 10.0;
+const BENCHMARK_FRAMES: usize = 1000;
+
+#[derive(Debug, Default, Resource)]
 pub struct DebugState {
     pub show_console: bool,
     pub show_debug: bool,
     pub show_admin: bool,
 }
 
-impl Default for DebugState {
-    fn default() -> Self {
-        Self {
-            show_console: false,
-            show_debug: false,
-            show_admin: false,
-        }
-    }
-}
-
 #[derive(Component)]
 pub struct DebugUI;
 
+#[derive(Debug)]
+enum BenchmarkPhase {
+    Start,
+    Warmup,
+    Measure,
+}
+
+// Замер отрисовки тайлов, включается переменной окружения TILE_BENCHMARK=mesh или TILE_BENCHMARK=sprites:
+// TILE_BENCHMARK=sprites cargo run --release
+// Мир заменяется на мир с BENCHMARK_SEED, а чанки читаются и пишутся во временный каталог,
+// так что сохранённые изменения слота в замер не попадают. Пока ресурс есть, save_game не пишет слот
+#[derive(Resource)]
+pub struct TileBenchmark {
+    mode: TileRenderMode,
+    phase: BenchmarkPhase,
+    warmup: Timer,
+    frame_times: Vec<f64>,
+}
+
 pub fn setup_debug(mut commands: Commands) {
     commands.init_resource::<DebugState>();
+
+    let mode = match std::env::var("TILE_BENCHMARK").as_deref() {
+        Ok("sprites") => TileRenderMode::Sprites,
+        Ok(_) => TileRenderMode::Mesh,
+        Err(_) => return,
+    };
+    commands.insert_resource(mode);
+    commands.insert_resource(TileBenchmark {
+        mode,
+        phase: BenchmarkPhase::Start,
+        warmup: Timer::from_seconds(BENCHMARK_WARMUP, TimerMode::Once),
+        frame_times: Vec::with_capacity(BENCHMARK_FRAMES),
+    });
+}
+
+// Переключает карту на мир замера с пустым временным хранилищем чанков
+pub fn start_tile_benchmark(
+    mut commands: Commands,
+    benchmark: Option<ResMut<TileBenchmark>>,
+    mut map_state: ResMut<MapState>,
+    mut persistence: ChunkPersistence,
+) {
+    let Some(mut benchmark) = benchmark.filter(|benchmark| matches!(benchmark.phase, BenchmarkPhase::Start)) else {
+        return;
+    };
+
+    let dir = std::env::temp_dir().join("tile_benchmark");
+    if dir.exists() {
+        if let Err(err) = std::fs::remove_dir_all(&dir) {
+            println!("Не удалось очистить {}: {}", dir.display(), err);
+        }
+    }
+    map_state.reset(&mut commands, BENCHMARK_SEED);
+    persistence.open(dir.join("chunks"), dir.join("unsaved_chunks"));
+    benchmark.phase = BenchmarkPhase::Warmup;
+}
+
+// Игрок стоит на месте, после прогрева собираются времена кадров, итог печатается, и игра закрывается
+pub fn tile_benchmark(
+    time: Res<Time>,
+    benchmark: Option<ResMut<TileBenchmark>>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+    entity_query: Query<(Has<ChunkTiles>, Has<TileSprite>)>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Some(mut benchmark) = benchmark else {
+        return;
+    };
+    for mut transform in player_query.iter_mut() {
+        transform.translation.x = 0.0;
+        transform.translation.y = 0.0;
+    }
+
+    match benchmark.phase {
+        BenchmarkPhase::Start => {}
+        BenchmarkPhase::Warmup => {
+            if benchmark.warmup.tick(time.raw_delta()).finished() {
+                benchmark.phase = BenchmarkPhase::Measure;
+            }
+        }
+        BenchmarkPhase::Measure => {
+            benchmark.frame_times.push(time.raw_delta_seconds_f64() * 1000.0);
+            if benchmark.frame_times.len() < BENCHMARK_FRAMES {
+                return;
+            }
+
+            let mut frame_times = std::mem::take(&mut benchmark.frame_times);
+            frame_times.sort_by(|a, b| a.total_cmp(b));
+            let average = frame_times.iter().sum::<f64>() / frame_times.len() as f64;
+            let p95 = frame_times[frame_times.len() * 95 / 100];
+            let worst = frame_times[frame_times.len() - 1];
+
+            let entities = entity_query.iter().count();
+            let chunks = entity_query.iter().filter(|(chunk, _)| *chunk).count();
+            let tile_entities = entity_query.iter().filter(|(chunk, sprite)| *chunk || *sprite).count();
+            println!(
+                "Замер тайлов ({:?}): чанков {}, сущностей {}, из них тайловых {}, кадр в среднем {:.2} мс, 95% {:.2} мс, худший {:.2} мс",
+                benchmark.mode, chunks, entities, tile_entities, average, p95, worst
+            );
+            exit.send(AppExit);
+        }
+    }
 }
 
 pub fn debug_input(
@@ -40,11 +146,37 @@ pub fn debug_input(
     }
 }
 
+// В админ-режиме F4 ставит под курсор врагов по очереди всех видов.
+// Враг принадлежит чанку под курсором и выгружается вместе с ним
+pub fn admin_spawn_enemy(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    debug_state: Res<DebugState>,
+    spawner: EnemySpawner,
+    cursor: CursorPosition,
+    mut next: Local<u16>,
+) {
+    let count = spawner.registry().count();
+    if !debug_state.show_admin || !keyboard.just_pressed(KeyCode::F4) || count == 0 {
+        return;
+    }
+    let Some(position) = cursor.world_position() else {
+        return;
+    };
+
+    let kind = EnemyType(*next % count as u16);
+    *next = next.wrapping_add(1);
+    if spawner.spawn(&mut commands, kind, position).is_none() {
+        println!("Чанк под курсором не загружен");
+    }
+}
+
 pub fn debug_ui(
     mut commands: Commands,
     debug_state: Res<DebugState>,
     diagnostics: Res<DiagnosticsStore>,
     player_query: Query<&Transform, With<crate::game::player::Player>>,
+    tiles: TileQuery,
     query: Query<Entity, With<DebugUI>>,
 ) {
     // Удаляем старый UI если он есть
@@ -57,11 +189,30 @@ pub fn debug_ui(
             .get(FrameTimeDiagnosticsPlugin::FPS)
             .and_then(|fps| fps.smoothed())
             .unwrap_or(0.0);
+        let frame_time = diagnostics
+            .get(FrameTimeDiagnosticsPlugin::FRAME_TIME)
+            .and_then(|frame_time| frame_time.smoothed())
+            .unwrap_or(0.0);
+        let entity_count = diagnostics
+            .get(EntityCountDiagnosticsPlugin::ENTITY_COUNT)
+            .and_then(|count| count.value())
+            .unwrap_or(0.0);
 
         let player_pos = player_query
             .get_single()
             .map(|transform| transform.translation)
             .unwrap_or_default();
+        let tile = tiles.tile_at_world(player_pos.truncate());
+        let biome = tiles.biome(TilePos::from_world(player_pos.truncate()));
+        let tile_text = match (tile, biome) {
+            (Some(tile), Some(biome)) => format!(
+                "Tile: {:?}, {}{}\n",
+                tile.tile_type,
+                biome.name,
+                if tile.walkable { "" } else { ", blocked" }
+            ),
+            _ => "Tile: -\n".to_string(),
+        };
 
         commands
             .spawn((
@@ -88,6 +239,14 @@ pub fn debug_ui(
                             ..default()
                         },
                     ),
+                    TextSection::new(
+                        format!("Frame: {:.2} ms\nEntities: {}\n", frame_time, entity_count),
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::GREEN,
+                            ..default()
+                        },
+                    ),
                     TextSection::new(
                         format!("Position: ({:.1}, {:.1})\n", player_pos.x, player_pos.y),
                         TextStyle {
@@ -96,6 +255,14 @@ pub fn debug_ui(
                             ..default()
                         },
                     ),
+                    TextSection::new(
+                        tile_text,
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::YELLOW,
+                            ..default()
+                        },
+                    ),
                 ]));
             });
     }
@@ -146,7 +313,7 @@ pub fn debug_ui(
             ))
             .with_children(|parent| {
                 parent.spawn(TextBundle::from_section(
-                    "Admin Menu",
+                    "Admin Menu\nF4 - враг под курсором",
                     TextStyle {
                         font_size: 20.0,
                         color: Color::WHITE,
@@ -0,0 +1,86 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::game::data::load_ron_dir;
+
+// Индекс декорации в реестре, декорации тоже сортируются по имени
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DecorationType(pub u16);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DropEntry {
+    // Имя предмета из assets/items
+    pub item: String,
+    pub min: u32,
+    pub max: u32,
+    // Вероятность, что запись вообще что-то даст
+    #[serde(default = "default_chance")]
+    pub chance: f32,
+}
+
+fn default_chance() -> f32 {
+    1.0
+}
+
+// Как декорацию добывают
+#[derive(Debug, Clone, Deserialize)]
+pub struct Harvest {
+    // Сколько ударов она выдерживает
+    pub durability: u32,
+    // Предмет, который должен быть в инвентаре
+    #[serde(default)]
+    pub tool: Option<String>,
+    pub drops: Vec<DropEntry>,
+    // Через сколько секунд добытая декорация вырастает снова. Без него она пропадает навсегда
+    #[serde(default)]
+    pub respawn_time: Option<f32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DecorationDefinition {
+    pub name: String,
+    pub texture: String,
+    // Радиус препятствия в тайлах, без него сквозь декорацию можно пройти
+    #[serde(default)]
+    pub collision_radius: Option<f32>,
+    #[serde(default)]
+    pub harvest: Option<Harvest>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DecorationRegistry {
+    decorations: Vec<DecorationDefinition>,
+}
+
+impl DecorationRegistry {
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        Self::from_definitions(load_ron_dir(dir)?)
+    }
+
+    pub fn from_definitions(mut decorations: Vec<DecorationDefinition>) -> Result<Self, String> {
+        if decorations.len() > u16::MAX as usize {
+            return Err(format!("слишком много декораций: {}", decorations.len()));
+        }
+        decorations.sort_by(|a, b| a.name.cmp(&b.name));
+
+        if let Some(pair) = decorations.windows(2).find(|pair| pair[0].name == pair[1].name) {
+            return Err(format!("декорация {} объявлена дважды", pair[0].name));
+        }
+
+        Ok(Self { decorations })
+    }
+
+    pub fn get(&self, decoration: DecorationType) -> &DecorationDefinition {
+        &self.decorations[decoration.0 as usize]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DecorationDefinition> {
+        self.decorations.iter()
+    }
+
+    pub fn find(&self, name: &str) -> Option<DecorationType> {
+        self.decorations
+            .binary_search_by(|decoration| decoration.name.as_str().cmp(name))
+            .ok()
+            .map(|index| DecorationType(index as u16))
+    }
+}
@@ -0,0 +1,400 @@
+use std::path::Path;
+use bevy::prelude::*;
+use bevy::ecs::query::Has;
+use bevy::ecs::system::SystemParam;
+use rand::Rng;
+use serde::Deserialize;
+use crate::game::collision::{move_and_slide, Collider};
+use crate::game::data::{assets_dir, load_ron_dir};
+use crate::game::health::{DamageEvent, DamageSource, Dead, Health};
+use crate::game::map::{MapState, SimulatedChunk, YSort};
+use crate::game::player::Player;
+use crate::game::tile_query::TileQuery;
+use crate::game::world::{ChunkPosition, TILE_SIZE};
+
+// Во сколько раз дальше радиуса восприятия враг ещё помнит игрока
+const LOSE_DISTANCE_FACTOR: f32 = 1.5;
+// Запас к дальности атаки, чтобы враг не дёргался между погоней и атакой на границе
+const ATTACK_RANGE_SLACK: f32 = 1.2;
+// Бродят враги медленнее, чем бегают
+const WANDER_SPEED_FACTOR: f32 = 0.4;
+const IDLE_TIME: (f32, f32) = (1.0, 4.0);
+// Если точка блуждания недостижима, враг бросает её через это время
+const WANDER_TIMEOUT: f32 = 6.0;
+// На каком расстоянии от точки блуждания она считается достигнутой
+const ARRIVE_DISTANCE: f32 = 4.0;
+
+// Индекс врага в реестре, враги сортируются по имени
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct EnemyType(pub u16);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemyDefinition {
+    pub name: String,
+    // Название для интерфейса, пока его нигде не показывают
+    #[allow(dead_code)]
+    pub title: String,
+    pub texture: String,
+    // Скорость бега в мировых единицах в секунду
+    pub speed: f32,
+    pub health: f32,
+    pub damage: f32,
+    // Расстояния в тайлах
+    pub attack_range: f32,
+    pub perception_radius: f32,
+    pub wander_radius: f32,
+    pub collision_radius: f32,
+    // Секунд между ударами
+    pub attack_cooldown: f32,
+    // Доля здоровья, ниже которой враг убегает. Ноль - дерётся до конца
+    #[serde(default)]
+    pub flee_health: f32,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct EnemyRegistry {
+    enemies: Vec<EnemyDefinition>,
+}
+
+impl EnemyRegistry {
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        Self::from_definitions(load_ron_dir(dir)?)
+    }
+
+    pub fn from_definitions(mut enemies: Vec<EnemyDefinition>) -> Result<Self, String> {
+        if enemies.len() > u16::MAX as usize {
+            return Err(format!("слишком много врагов: {}", enemies.len()));
+        }
+        enemies.sort_by(|a, b| a.name.cmp(&b.name));
+
+        if let Some(pair) = enemies.windows(2).find(|pair| pair[0].name == pair[1].name) {
+            return Err(format!("враг {} объявлен дважды", pair[0].name));
+        }
+        if let Some(enemy) = enemies.iter().find(|enemy| enemy.health <= 0.0 || enemy.attack_cooldown <= 0.0) {
+            return Err(format!("враг {}: здоровье и пауза между ударами должны быть больше нуля", enemy.name));
+        }
+
+        Ok(Self { enemies })
+    }
+
+    pub fn get(&self, enemy: EnemyType) -> &EnemyDefinition {
+        &self.enemies[enemy.0 as usize]
+    }
+
+    pub fn count(&self) -> usize {
+        self.enemies.len()
+    }
+
+    pub fn find(&self, name: &str) -> Option<EnemyType> {
+        self.enemies
+            .binary_search_by(|enemy| enemy.name.as_str().cmp(name))
+            .ok()
+            .map(|index| EnemyType(index as u16))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnemyState {
+    Idle,
+    // Идёт к случайной точке рядом с домом
+    Wander(Vec2),
+    Chase,
+    Attack,
+    Flee,
+}
+
+#[derive(Component)]
+pub struct Enemy {
+    pub kind: EnemyType,
+    pub state: EnemyState,
+    // Вокруг этой точки враг бродит, когда никого не видит
+    pub home: Vec2,
+    // Сколько ещё стоять на месте или идти к точке блуждания
+    state_timer: Timer,
+    attack_cooldown: Timer,
+}
+
+impl Enemy {
+    pub fn new(kind: EnemyType, home: Vec2, attack_cooldown: f32) -> Self {
+        let mut attack_cooldown = Timer::from_seconds(attack_cooldown, TimerMode::Once);
+        attack_cooldown.tick(attack_cooldown.duration());
+        Self {
+            kind,
+            state: EnemyState::Idle,
+            home,
+            state_timer: idle_timer(),
+            attack_cooldown,
+        }
+    }
+}
+
+fn idle_timer() -> Timer {
+    Timer::from_seconds(rand::thread_rng().gen_range(IDLE_TIME.0..IDLE_TIME.1), TimerMode::Once)
+}
+
+pub fn setup_enemies(mut commands: Commands) {
+    let registry = EnemyRegistry::load(&assets_dir().join("enemies"))
+        .unwrap_or_else(|err| panic!("Не удалось загрузить врагов: {}", err));
+    commands.insert_resource(registry);
+}
+
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    registry: &EnemyRegistry,
+    kind: EnemyType,
+    position: Vec2,
+) -> Entity {
+    let definition = registry.get(kind);
+    commands
+        .spawn((
+            SpriteBundle {
+                transform: Transform::from_xyz(position.x, position.y, 1.0),
+                texture: asset_server.load(definition.texture.as_str()),
+                sprite: Sprite {
+                    custom_size: Some(Vec2::splat(TILE_SIZE)),
+                    ..default()
+                },
+                ..default()
+            },
+            Enemy::new(kind, position, definition.attack_cooldown),
+            Health::new(definition.health),
+            Collider { radius: definition.collision_radius * TILE_SIZE },
+            YSort,
+        ))
+        .id()
+}
+
+// Всё, что нужно, чтобы поставить врага в загруженный мир
+#[derive(SystemParam)]
+pub struct EnemySpawner<'w> {
+    asset_server: Res<'w, AssetServer>,
+    registry: Res<'w, EnemyRegistry>,
+    map_state: Res<'w, MapState>,
+}
+
+impl EnemySpawner<'_> {
+    pub fn registry(&self) -> &EnemyRegistry {
+        &self.registry
+    }
+
+    // Ставит врага и сразу отдаёт его чанку, в котором он стоит. В незагруженный чанк врага не поставить
+    pub fn spawn(&self, commands: &mut Commands, kind: EnemyType, position: Vec2) -> Option<Entity> {
+        let chunk = self.map_state.chunk_entity(ChunkPosition::from_world(position))?;
+        let entity = spawn_enemy(commands, &self.asset_server, &self.registry, kind, position);
+        commands.entity(entity).set_parent(chunk);
+        Some(entity)
+    }
+}
+
+// Где враги живут: тайлы, по которым они ходят, и чанки в радиусе симуляции
+#[derive(SystemParam)]
+pub struct EnemyArea<'w, 's> {
+    tiles: TileQuery<'w, 's>,
+    simulated_query: Query<'w, 's, (), With<SimulatedChunk>>,
+}
+
+impl EnemyArea<'_, '_> {
+    // Враги на чанках за радиусом симуляции замирают. Ещё не отданный чанку враг тоже ждёт
+    fn simulated(&self, parent: Option<&Parent>) -> bool {
+        parent.is_some_and(|parent| self.simulated_query.contains(parent.get()))
+    }
+}
+
+// Видно ли to из from: отрезок между ними не должен задевать ни одного препятствия
+fn line_of_sight(from: Vec2, to: Vec2, mut obstacles: impl Iterator<Item = (Vec2, f32)>) -> bool {
+    let segment = to - from;
+    let length_squared = segment.length_squared();
+    obstacles.all(|(center, radius)| {
+        let t = if length_squared > 0.0 { ((center - from).dot(segment) / length_squared).clamp(0.0, 1.0) } else { 0.0 };
+        (from + segment * t).distance(center) >= radius
+    })
+}
+
+// Что враг знает об игроке и о себе на этом кадре
+#[derive(Debug, Clone, Copy, Default)]
+struct Senses {
+    distance: Option<f32>,
+    sees_player: bool,
+    remembers_player: bool,
+    hurt: bool,
+    timer_finished: bool,
+}
+
+// Состояние врага зависит от расстояния до игрока, прямой видимости и собственного здоровья.
+// Точка блуждания случайная, поэтому её выбирает вызывающий
+fn next_state(state: EnemyState, position: Vec2, senses: Senses, attack_range: f32, wander: impl FnOnce() -> Vec2) -> EnemyState {
+    match state {
+        EnemyState::Flee if senses.remembers_player => EnemyState::Flee,
+        _ if senses.hurt && senses.sees_player => EnemyState::Flee,
+        EnemyState::Idle | EnemyState::Wander(_) if senses.sees_player => EnemyState::Chase,
+        EnemyState::Idle if senses.timer_finished => EnemyState::Wander(wander()),
+        EnemyState::Wander(target) if target.distance(position) < ARRIVE_DISTANCE || senses.timer_finished => EnemyState::Idle,
+        EnemyState::Chase | EnemyState::Attack if !senses.remembers_player => EnemyState::Idle,
+        // Бьют только того, кого видят: через стену враг не достанет
+        EnemyState::Chase if senses.sees_player && senses.distance.is_some_and(|distance| distance <= attack_range) => EnemyState::Attack,
+        EnemyState::Chase if !senses.sees_player => EnemyState::Idle,
+        EnemyState::Attack if !senses.sees_player || senses.distance.is_none_or(|distance| distance > attack_range * ATTACK_RANGE_SLACK) => {
+            EnemyState::Chase
+        }
+        EnemyState::Flee => EnemyState::Idle,
+        state => state,
+    }
+}
+
+pub fn enemy_ai(
+    time: Res<Time>,
+    registry: Res<EnemyRegistry>,
+    area: EnemyArea,
+    player_query: Query<(Entity, &Transform), (With<Player>, Without<Dead>)>,
+    collider_query: Query<(Entity, &GlobalTransform, &Collider, Has<Enemy>)>,
+    mut enemy_query: Query<(Entity, &mut Transform, &mut Enemy, &Health, Option<&Parent>), Without<Player>>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    let player = player_query.get_single().ok().map(|(entity, transform)| (entity, transform.translation.truncate()));
+    let mut rng = rand::thread_rng();
+
+    for (entity, mut transform, mut enemy, health, parent) in enemy_query.iter_mut() {
+        if !area.simulated(parent) {
+            continue;
+        }
+        let definition = registry.get(enemy.kind);
+        let position = transform.translation.truncate();
+        enemy.state_timer.tick(time.delta());
+        enemy.attack_cooldown.tick(time.delta());
+
+        let perception = definition.perception_radius * TILE_SIZE;
+        let attack_range = definition.attack_range * TILE_SIZE;
+        let distance = player.map(|(_, player_position)| player_position.distance(position));
+        // Сквозь других врагов видно, сквозь деревья, камни и стены - нет
+        let sees_player = match (player, distance) {
+            (Some((player_entity, player_position)), Some(distance)) if distance <= perception => {
+                let obstacles = collider_query
+                    .iter()
+                    .filter(|(other, _, _, is_enemy)| *other != entity && *other != player_entity && !is_enemy)
+                    .map(|(_, transform, collider, _)| (transform.translation().truncate(), collider.radius));
+                line_of_sight(position, player_position, obstacles)
+            }
+            _ => false,
+        };
+        let senses = Senses {
+            distance,
+            sees_player,
+            remembers_player: distance.is_some_and(|distance| distance <= perception * LOSE_DISTANCE_FACTOR),
+            hurt: health.current <= health.max * definition.flee_health,
+            timer_finished: enemy.state_timer.finished(),
+        };
+        let next = next_state(enemy.state, position, senses, attack_range, || {
+            let offset = Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+            enemy.home + offset * definition.wander_radius * TILE_SIZE
+        });
+        if next != enemy.state {
+            enemy.state_timer = match next {
+                EnemyState::Wander(_) => Timer::from_seconds(WANDER_TIMEOUT, TimerMode::Once),
+                _ => idle_timer(),
+            };
+            // Враг заводит новый дом там, где закончил погоню
+            if next == EnemyState::Idle && matches!(enemy.state, EnemyState::Chase | EnemyState::Attack | EnemyState::Flee) {
+                enemy.home = position;
+            }
+            enemy.state = next;
+        }
+
+        let player_position = player.map(|(_, player_position)| player_position).unwrap_or(position);
+        let (direction, speed) = match enemy.state {
+            EnemyState::Idle => (Vec2::ZERO, 0.0),
+            EnemyState::Wander(target) => ((target - position).normalize_or_zero(), definition.speed * WANDER_SPEED_FACTOR),
+            EnemyState::Chase => ((player_position - position).normalize_or_zero(), definition.speed),
+            EnemyState::Flee => ((position - player_position).normalize_or_zero(), definition.speed),
+            EnemyState::Attack => {
+                if let Some((player_entity, _)) = player.filter(|_| enemy.attack_cooldown.finished()) {
+                    damage_events.send(DamageEvent {
+                        target: player_entity,
+                        amount: definition.damage,
+                        source: DamageSource::Enemy,
+                    });
+                    enemy.attack_cooldown.reset();
+                }
+                (Vec2::ZERO, 0.0)
+            }
+        };
+
+        if direction != Vec2::ZERO {
+            let colliders = collider_query
+                .iter()
+                .filter(|(other, _, _, _)| *other != entity)
+                .map(|(_, transform, collider, _)| (transform.translation().truncate(), collider));
+            let delta = direction * speed * time.delta_seconds();
+            let moved = move_and_slide(&area.tiles, colliders, position, delta, definition.collision_radius * TILE_SIZE);
+            transform.translation.x = moved.x;
+            transform.translation.y = moved.y;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ATTACK_RANGE: f32 = 16.0;
+
+    fn step(state: EnemyState, senses: Senses) -> EnemyState {
+        next_state(state, Vec2::ZERO, senses, ATTACK_RANGE, || Vec2::new(100.0, 0.0))
+    }
+
+    fn seen_at(distance: f32) -> Senses {
+        Senses {
+            distance: Some(distance),
+            sees_player: true,
+            remembers_player: true,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn obstacle_on_segment_blocks_sight() {
+        let from = Vec2::ZERO;
+        let to = Vec2::new(100.0, 0.0);
+        assert!(line_of_sight(from, to, std::iter::empty()), "без препятствий игрока видно");
+        assert!(!line_of_sight(from, to, [(Vec2::new(50.0, 5.0), 8.0)].into_iter()), "круг на отрезке закрывает обзор");
+        assert!(line_of_sight(from, to, [(Vec2::new(50.0, 20.0), 8.0)].into_iter()), "круг сбоку от отрезка не мешает");
+        assert!(line_of_sight(from, to, [(Vec2::new(-20.0, 0.0), 8.0)].into_iter()), "круг за спиной не мешает");
+    }
+
+    #[test]
+    fn chases_attacks_and_chases_again() {
+        let state = step(EnemyState::Idle, seen_at(64.0));
+        assert_eq!(state, EnemyState::Chase, "увидев игрока, враг гонится за ним");
+        let state = step(state, seen_at(ATTACK_RANGE));
+        assert_eq!(state, EnemyState::Attack, "догнав, враг атакует");
+        let state = step(state, seen_at(ATTACK_RANGE * ATTACK_RANGE_SLACK * 0.9));
+        assert_eq!(state, EnemyState::Attack, "у границы дальности враг не бросает атаку");
+        let state = step(state, seen_at(ATTACK_RANGE * ATTACK_RANGE_SLACK * 2.0));
+        assert_eq!(state, EnemyState::Chase, "когда игрок отошёл, враг снова гонится");
+    }
+
+    #[test]
+    fn does_not_attack_without_sight() {
+        let hidden = Senses { sees_player: false, ..seen_at(ATTACK_RANGE) };
+        assert_eq!(step(EnemyState::Chase, hidden), EnemyState::Idle, "через стену враг не атакует");
+        assert_eq!(step(EnemyState::Attack, hidden), EnemyState::Chase, "потеряв игрока из виду, враг перестаёт бить");
+    }
+
+    #[test]
+    fn flees_when_hurt() {
+        let hurt = Senses { hurt: true, ..seen_at(64.0) };
+        assert_eq!(step(EnemyState::Chase, hurt), EnemyState::Flee, "раненый враг убегает");
+        assert_eq!(step(EnemyState::Idle, hurt), EnemyState::Flee, "раненый враг не нападает");
+        let away = Senses { sees_player: false, ..hurt };
+        assert_eq!(step(EnemyState::Flee, away), EnemyState::Flee, "пока игрок рядом, враг бежит дальше");
+        let lost = Senses { remembers_player: false, ..away };
+        assert_eq!(step(EnemyState::Flee, lost), EnemyState::Idle, "оторвавшись, враг успокаивается");
+    }
+
+    #[test]
+    fn idle_wanders_after_timer() {
+        assert_eq!(step(EnemyState::Idle, Senses::default()), EnemyState::Idle, "пока таймер идёт, враг стоит");
+        let finished = Senses { timer_finished: true, ..default() };
+        assert_eq!(step(EnemyState::Idle, finished), EnemyState::Wander(Vec2::new(100.0, 0.0)), "потом враг идёт бродить");
+    }
+}
@@ -1,107 +1,411 @@
-use rand::Rng;
-use noise::{NoiseFn, Perlin};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use crate::game::biome::{BiomeRegistry, BiomeType, GroundKind};
+use crate::game::decoration::DecorationType;
+use crate::game::rivers::river_tiles;
+use crate::game::roads::{road_tiles, RoadPiece};
+use crate::game::structure::StructureType;
+use crate::game::terrain::TerrainGenerator;
+use crate::game::world::{ChunkPosition, TilePos, CHUNK_SIZE};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum BiomeType {
-    Summer,
-    Winter,
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TileType {
+    Grass { rotation: f32, variant: u8 },
+    Water,
+    Dirt,
+    Road { piece: RoadPiece, rotation: f32, variant: u8 },
+    // Дорога над рекой или озером
+    Bridge { rotation: f32 },
+    // Берег, rotation смотрит в сторону воды
+    Shore { rotation: f32 },
+    BiomeBorder { from: BiomeType, to: BiomeType, rotation: f32 },
 }
 
-impl BiomeType {
-    pub fn get_temperature(&self) -> f32 {
-        match self {
-            BiomeType::Summer => 0.7,
-            BiomeType::Winter => 0.3,
-        }
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tile {
+    pub tile_type: TileType,
+    pub position: TilePos,
+    pub biome: BiomeType,
+    pub elevation: f32,
+}
+
+impl TileType {
+    // Декорации ставятся только на открытую землю
+    pub fn allows_decorations(&self) -> bool {
+        matches!(self, TileType::Grass { .. } | TileType::Dirt | TileType::BiomeBorder { .. })
     }
 
-    pub fn get_humidity(&self) -> f32 {
+    // По воде пешком не пройти, мосты и берега проходимы
+    pub fn is_walkable(&self) -> bool {
+        !matches!(self, TileType::Water)
+    }
+
+    // Множитель скорости передвижения по тайлу
+    pub fn speed_multiplier(&self) -> f32 {
         match self {
-            BiomeType::Summer => 0.5,
-            BiomeType::Winter => 0.3,
+            TileType::Road { .. } | TileType::Bridge { .. } => 1.4,
+            TileType::Dirt => 0.8,
+            _ => 1.0,
         }
     }
 }
 
-pub fn determine_biome(temperature: f32, _humidity: f32) -> BiomeType {
-    if temperature > 0.5 {
-        BiomeType::Summer
-    } else {
-        BiomeType::Winter
-    }
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Decoration {
+    pub kind: DecorationType,
+    // Позиция в тайлах, центр тайла приходится на целые координаты
+    pub position: (f32, f32),
+    // Сколько ударов декорация уже получила
+    pub hits: u32,
+    // Добытая декорация не видна до этого момента мирового времени
+    pub respawn_at: Option<f64>,
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum TileType {
-    Grass { rotation: f32 },
-    Water,
-    Dirt,
-    Road,
-    BiomeBorder { from: BiomeType, to: BiomeType },
+// Постройка игрока, стоит в центре тайла
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Structure {
+    pub kind: StructureType,
+    pub position: TilePos,
+    pub health: f32,
 }
 
-#[derive(Debug)]
-pub struct Tile {
-    pub tile_type: TileType,
-    pub position: (i32, i32),
-    pub biome: BiomeType,
+// Предмет, лежащий на земле. Хранится по имени, как инвентарь в сохранении
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DroppedItem {
+    pub item: String,
+    pub count: u32,
+    // Позиция в тайлах, как у декораций
+    pub position: (f32, f32),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct ChunkPosition(pub i32, pub i32);
+// Поставленная игроком станция, тоже по имени
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlacedStation {
+    pub station: String,
+    pub position: TilePos,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkData {
+    pub tiles: Vec<Tile>,
+    pub decorations: Vec<Decoration>,
+    pub structures: Vec<Structure>,
+    pub items: Vec<DroppedItem>,
+    pub stations: Vec<PlacedStation>,
+}
+
+// Декорации не подходят к краю чанка ближе этого расстояния, чтобы их тайл всегда лежал в этом чанке.
+// От соседних чанков отступ не спасает: разрежение видит только декорации своего чанка,
+// поэтому у шва декорации могут стоять ближе своего min_distance
+const DECORATION_EDGE_MARGIN: f32 = 0.5;
+
+// Соседи по порядку проверки и поворот перехода в их сторону (в градусах)
+const NEIGHBOURS: [((i32, i32), f32); 4] = [
+    ((1, 0), 0.0),
+    ((0, 1), 90.0),
+    ((-1, 0), 180.0),
+    ((0, -1), 270.0),
+];
 
-const CHUNK_SIZE: i32 = 16;
+// Сид чанка зависит только от сида мира и позиции чанка,
+// поэтому один и тот же чанк всегда генерируется одинаково
+pub fn chunk_seed(seed: u64, chunk_pos: ChunkPosition) -> u64 {
+    let mut hash = seed;
+    hash ^= (chunk_pos.0 as u32 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    hash = splitmix64(hash);
+    hash ^= (chunk_pos.1 as u32 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    splitmix64(hash)
+}
+
+// ChaCha8 выдаёт одну и ту же последовательность на всех платформах и версиях rand
+pub fn chunk_rng(seed: u64, chunk_pos: ChunkPosition) -> ChaCha8Rng {
+    ChaCha8Rng::seed_from_u64(chunk_seed(seed, chunk_pos))
+}
+
+// Хеш для объектов, привязанных к клетке сетки (регионы дорог, истоки рек)
+pub(crate) fn grid_hash(seed: u64, cell: (i32, i32), salt: u64) -> u64 {
+    let mut hash = splitmix64(seed ^ salt.wrapping_mul(0xD6E8_FEB8_6659_FD93));
+    hash = splitmix64(hash ^ cell.0 as u32 as u64);
+    splitmix64(hash ^ ((cell.1 as u32 as u64) << 32))
+}
 
-pub fn generate_chunk(chunk_pos: ChunkPosition, seed: u64) -> Vec<Tile> {
+pub(crate) fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+// Переход рисуется только с одной стороны шва, иначе граница получается в два тайла.
+// Его берёт биом с меньшим индексом, а если у него нет текстуры перехода, то сосед
+fn draws_border(biomes: &BiomeRegistry, biome: BiomeType, neighbour: BiomeType) -> bool {
+    if neighbour == biome || biomes.border_texture(biome, neighbour).is_none() {
+        return false;
+    }
+    biome.0 < neighbour.0 || biomes.border_texture(neighbour, biome).is_none()
+}
+
+pub fn generate_chunk(generator: &TerrainGenerator, biomes: &BiomeRegistry, chunk_pos: ChunkPosition) -> ChunkData {
     let mut tiles = Vec::new();
-    let mut rng = rand::thread_rng();
-    
-    let biome = if noise_2d(chunk_pos.0 as f32 * 0.1, chunk_pos.1 as f32 * 0.1, seed) > 0.5 {
-        BiomeType::Summer
-    } else {
-        BiomeType::Winter
+    let mut rng = chunk_rng(generator.seed(), chunk_pos);
+
+    // Биомы и вода считаются с запасом в один тайл вокруг чанка,
+    // чтобы переходы и берега на краю видели соседний чанк
+    let TilePos(origin_x, origin_y) = chunk_pos.origin();
+    let margin_size = CHUNK_SIZE + 2;
+    let mut samples = Vec::with_capacity((margin_size * margin_size) as usize);
+    for y in -1..=CHUNK_SIZE {
+        for x in -1..=CHUNK_SIZE {
+            let sample = generator.sample(origin_x + x, origin_y + y);
+            samples.push((sample, biomes.determine_biome(sample.temperature, sample.humidity)));
+        }
+    }
+    let sample_at = |local_x: i32, local_y: i32| samples[((local_y + 1) * margin_size + local_x + 1) as usize];
+    let roads = road_tiles(generator, chunk_pos);
+    let rivers = river_tiles(
+        generator,
+        (origin_x - 1, origin_y - 1),
+        (origin_x + CHUNK_SIZE, origin_y + CHUNK_SIZE),
+    );
+    let is_water = |local_x: i32, local_y: i32| {
+        generator.is_water(&sample_at(local_x, local_y).0)
+            || rivers.contains(&(origin_x + local_x, origin_y + local_y))
     };
 
     for local_y in 0..CHUNK_SIZE {
         for local_x in 0..CHUNK_SIZE {
-            let world_x = chunk_pos.0 * CHUNK_SIZE + local_x;
-            let world_y = chunk_pos.1 * CHUNK_SIZE + local_y;
+            let position = chunk_pos.tile(local_x, local_y);
+            let (world_x, world_y) = (position.0, position.1);
+
+            // Биом и высота берутся из шума для каждого тайла отдельно
+            let (sample, biome) = sample_at(local_x, local_y);
 
-            let tile_type = if world_x % 32 == 0 {
-                TileType::Road
+            // Кубики бросаются для каждого тайла в одном порядке, даже если
+            // результат не нужен, чтобы дорога не сдвигала последовательность
+            let chance: f32 = rng.gen();
+            let variant: u8 = rng.gen();
+
+            let tile_type = if let Some(road) = roads.get(&(world_x, world_y)) {
+                if is_water(local_x, local_y) {
+                    TileType::Bridge { rotation: road.rotation }
+                } else {
+                    TileType::Road { piece: road.piece, rotation: road.rotation, variant: road.variant }
+                }
+            } else if is_water(local_x, local_y) {
+                TileType::Water
+            } else if let Some(&(_, rotation)) = NEIGHBOURS.iter()
+                .find(|&&((dx, dy), _)| is_water(local_x + dx, local_y + dy))
+                .filter(|_| biomes.get(biome).textures.shore.is_some())
+            {
+                TileType::Shore { rotation }
+            } else if let Some((to, rotation)) = NEIGHBOURS.iter()
+                .map(|&((dx, dy), rotation)| (sample_at(local_x + dx, local_y + dy).1, rotation))
+                .find(|&(neighbour, _)| draws_border(biomes, biome, neighbour))
+            {
+                TileType::BiomeBorder { from: biome, to, rotation }
             } else {
-                let chance: f32 = rng.gen();
-                match biome {
-                    BiomeType::Summer => {
-                        if chance < 0.05 {
-                            TileType::Water
-                        } else if chance < 0.3 {
-                            TileType::Dirt
-                        } else {
-                            TileType::Grass { rotation: 0.0 }
-                        }
-                    }
-                    BiomeType::Winter => {
-                        if chance < 0.05 {
-                            TileType::Water
-                        } else {
-                            TileType::Grass { rotation: 0.0 }
-                        }
-                    }
+                match biomes.get(biome).roll_ground(chance) {
+                    GroundKind::Dirt => TileType::Dirt,
+                    GroundKind::Grass => TileType::Grass { rotation: 0.0, variant },
                 }
             };
 
             tiles.push(Tile {
                 tile_type,
-                position: (world_x, world_y),
+                position,
                 biome,
+                elevation: sample.elevation,
             });
         }
     }
 
-    tiles
+    let decorations = place_decorations(&mut rng, biomes, &tiles, (origin_x, origin_y));
+    ChunkData {
+        tiles,
+        decorations,
+        structures: Vec::new(),
+        items: Vec::new(),
+        stations: Vec::new(),
+    }
 }
 
-fn noise_2d(x: f32, y: f32, seed: u64) -> f32 {
-    (x.sin() + y.cos() + seed as f32).sin() * 0.5 + 0.5
+// Пуассоновское разбрасывание: случайные точки отбрасываются,
+// если рядом уже стоит декорация ближе минимального расстояния
+fn place_decorations(
+    rng: &mut ChaCha8Rng,
+    biomes: &BiomeRegistry,
+    tiles: &[Tile],
+    origin: (i32, i32),
+) -> Vec<Decoration> {
+    let mut placed: Vec<(Decoration, f32)> = Vec::new();
+    let min = -0.5 + DECORATION_EDGE_MARGIN;
+    let max = CHUNK_SIZE as f32 - 0.5 - DECORATION_EDGE_MARGIN;
+
+    // В среднем одна попытка на тайл, тогда density — это доля занятых тайлов
+    for _ in 0..CHUNK_SIZE * CHUNK_SIZE {
+        let local_x: f32 = rng.gen_range(min..max);
+        let local_y: f32 = rng.gen_range(min..max);
+        let roll: f32 = rng.gen();
+
+        let tile = &tiles[(local_y.round() as i32 * CHUNK_SIZE + local_x.round() as i32) as usize];
+        if !tile.tile_type.allows_decorations() {
+            continue;
+        }
+        let Some(entry) = biomes.get(tile.biome).pick_decoration(roll) else {
+            continue;
+        };
+
+        let position = (origin.0 as f32 + local_x, origin.1 as f32 + local_y);
+        let crowded = placed.iter().any(|(other, other_distance)| {
+            let distance = entry.min_distance.max(*other_distance);
+            (other.position.0 - position.0).powi(2) + (other.position.1 - position.1).powi(2) < distance * distance
+        });
+        if crowded {
+            continue;
+        }
+
+        let decoration = Decoration {
+            kind: entry.kind,
+            position,
+            hits: 0,
+            respawn_at: None,
+        };
+        placed.push((decoration, entry.min_distance));
+    }
+
+    placed.into_iter().map(|(decoration, _)| decoration).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+    use crate::game::data::assets_dir;
+    use crate::game::decoration::DecorationRegistry;
+    use crate::game::terrain::TerrainSettings;
+
+    const SEED: u64 = 12345;
+    // Чанки по обе стороны от нуля, чтобы поймать ошибки округления отрицательных координат
+    const CHUNKS: [ChunkPosition; 5] = [
+        ChunkPosition(0, 0),
+        ChunkPosition(3, -2),
+        ChunkPosition(-1, -1),
+        ChunkPosition(-7, 5),
+        ChunkPosition(40, -33),
+    ];
+
+    fn biomes() -> BiomeRegistry {
+        let decorations = DecorationRegistry::load(&assets_dir().join("decorations")).unwrap();
+        BiomeRegistry::load(&assets_dir().join("biomes"), &decorations).unwrap()
+    }
+
+    // DefaultHasher может поменяться между версиями Rust, поэтому хеш считается своим splitmix64.
+    // Генератор ставит только тайлы и декорации, остальные поля ChunkData в хеш не входят,
+    // чтобы новые сохраняемые поля не меняли эталонные значения
+    fn chunk_hash(chunk: &ChunkData) -> u64 {
+        let bytes = bincode::serialize(&(&chunk.tiles, &chunk.decorations)).unwrap();
+        bytes.chunks(8).fold(bytes.len() as u64, |hash, word| {
+            let mut buffer = [0; 8];
+            buffer[..word.len()].copy_from_slice(word);
+            splitmix64(hash ^ u64::from_le_bytes(buffer))
+        })
+    }
+
+    #[test]
+    fn same_seed_gives_same_chunk() {
+        let biomes = biomes();
+        let first = TerrainGenerator::new(SEED, TerrainSettings::default());
+        let second = TerrainGenerator::new(SEED, TerrainSettings::default());
+
+        for chunk_pos in CHUNKS {
+            assert_eq!(generate_chunk(&first, &biomes, chunk_pos), generate_chunk(&second, &biomes, chunk_pos));
+        }
+    }
+
+    // На шве между биомами переход стоит только с одной стороны
+    #[test]
+    fn biome_border_is_one_tile_wide() {
+        let biomes = biomes();
+        let generator = TerrainGenerator::new(SEED, TerrainSettings::default());
+        let mut tiles = HashMap::new();
+        for y in -1..=1 {
+            for x in -1..=1 {
+                for tile in generate_chunk(&generator, &biomes, ChunkPosition(x, y)).tiles {
+                    tiles.insert(tile.position, tile);
+                }
+            }
+        }
+
+        let mut borders = 0;
+        for tile in tiles.values() {
+            let TileType::BiomeBorder { to, .. } = tile.tile_type else {
+                continue;
+            };
+            borders += 1;
+            for ((dx, dy), _) in NEIGHBOURS {
+                let Some(neighbour) = tiles.get(&tile.position.offset(dx, dy)) else {
+                    continue;
+                };
+                if neighbour.biome == to {
+                    assert!(
+                        !matches!(neighbour.tile_type, TileType::BiomeBorder { to, .. } if to == tile.biome),
+                        "двойной переход на {:?}",
+                        tile.position
+                    );
+                }
+            }
+        }
+        assert!(borders > 0, "в проверяемых чанках нет ни одного шва");
+    }
+
+    #[test]
+    fn chunk_seed_depends_on_both_coordinates() {
+        let seeds: Vec<u64> = [(0, 0), (1, 0), (0, 1), (-1, 0), (0, -1), (1, -1), (-1, 1)]
+            .into_iter()
+            .map(|(x, y)| chunk_seed(SEED, ChunkPosition(x, y)))
+            .collect();
+
+        for (index, seed) in seeds.iter().enumerate() {
+            assert!(!seeds[index + 1..].contains(seed), "совпали сиды чанков: {:?}", seeds);
+        }
+        assert_ne!(chunk_seed(SEED, ChunkPosition(2, 3)), chunk_seed(SEED + 1, ChunkPosition(2, 3)));
+    }
+
+    #[test]
+    fn chunk_seed_golden() {
+        let seeds: Vec<u64> = CHUNKS.iter().map(|chunk_pos| chunk_seed(SEED, *chunk_pos)).collect();
+        assert_eq!(
+            seeds,
+            [
+                291995243589385535,
+                9535223757483185140,
+                919336475412822073,
+                4538966946109202338,
+                14526873697604506539,
+            ]
+        );
+    }
+
+    // Если генерация поменялась намеренно, хеши нужно обновить вместе с изменением.
+    // Хеши зависят и от assets/biomes с assets/decorations
+    #[test]
+    fn generate_chunk_golden() {
+        let biomes = biomes();
+        let generator = TerrainGenerator::new(SEED, TerrainSettings::default());
+        let hashes: Vec<u64> = CHUNKS
+            .iter()
+            .map(|chunk_pos| chunk_hash(&generate_chunk(&generator, &biomes, *chunk_pos)))
+            .collect();
+        assert_eq!(
+            hashes,
+            [
+                5876201672075228469,
+                1520427558063828585,
+                17690072584565643772,
+                3315091289428752799,
+                16446641729906049198,
+            ]
+        );
+    }
 }
@@ -0,0 +1,187 @@
+use std::f32::consts::PI;
+use bevy::prelude::*;
+use rand::Rng;
+use crate::game::bullet::CursorPosition;
+use crate::game::clock::WorldClock;
+use crate::game::collision::Collider;
+use crate::game::health::Dead;
+use crate::game::inventory::{Inventory, ItemDrops};
+use crate::game::item::ItemStack;
+use crate::game::map::{ChunkModified, Decoration, MapState};
+use crate::game::menu::GameState;
+use crate::game::player::Player;
+use crate::game::world::TILE_SIZE;
+
+// Пауза между ударами, в секундах
+const HARVEST_COOLDOWN: f64 = 0.4;
+// Как далеко от игрока можно бить, в мировых единицах
+const HARVEST_REACH: f32 = TILE_SIZE * 1.5;
+// Насколько близко к декорации должен быть курсор
+const HARVEST_PICK_RADIUS: f32 = TILE_SIZE * 0.5;
+const HIT_ANIMATION_TIME: f32 = 0.2;
+// Насколько декорация сжимается при ударе
+const HIT_SQUASH: f32 = 0.2;
+const DROP_PICKUP_DELAY: f32 = 0.3;
+
+// Добытая декорация, которая ждёт восстановления
+#[derive(Component)]
+pub struct Depleted;
+
+// Кто может добывать декорации
+#[derive(Component, Default)]
+pub struct Harvester {
+    // Мировое время последнего удара
+    last_hit: f64,
+}
+
+#[derive(Component)]
+pub struct HitAnimation(Timer);
+
+#[derive(Event)]
+pub struct HarvestHit {
+    pub target: Entity,
+}
+
+// Правая кнопка бьёт декорацию под курсором, если она рядом с игроком
+pub fn harvest_input(
+    mouse: Res<Input<MouseButton>>,
+    game_state: Res<GameState>,
+    clock: Res<WorldClock>,
+    cursor: CursorPosition,
+    mut player_query: Query<(&Transform, &mut Harvester), (With<Player>, Without<Dead>)>,
+    decoration_query: Query<(Entity, &GlobalTransform), (With<Decoration>, Without<Depleted>)>,
+    mut hit_events: EventWriter<HarvestHit>,
+) {
+    if game_state.paused || game_state.pointer_over_ui || game_state.building || !mouse.pressed(MouseButton::Right) {
+        return;
+    }
+    let Ok((transform, mut harvester)) = player_query.get_single_mut() else {
+        return;
+    };
+    if clock.elapsed - harvester.last_hit < HARVEST_COOLDOWN {
+        return;
+    }
+    let Some(cursor) = cursor.world_position() else {
+        return;
+    };
+    let position = transform.translation.truncate();
+
+    let target = decoration_query
+        .iter()
+        .map(|(entity, transform)| (entity, transform.translation().truncate()))
+        .filter(|(_, center)| center.distance(cursor) <= HARVEST_PICK_RADIUS && center.distance(position) <= HARVEST_REACH)
+        .min_by(|(_, a), (_, b)| a.distance(cursor).total_cmp(&b.distance(cursor)));
+    if let Some((target, _)) = target {
+        harvester.last_hit = clock.elapsed;
+        hit_events.send(HarvestHit { target });
+    }
+}
+
+pub fn apply_harvest_hits(
+    mut commands: Commands,
+    mut hit_events: EventReader<HarvestHit>,
+    clock: Res<WorldClock>,
+    map_state: Res<MapState>,
+    drops: ItemDrops,
+    player_query: Query<&Inventory, With<Player>>,
+    mut decoration_query: Query<(&mut Decoration, &GlobalTransform, &Parent), Without<Depleted>>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for event in hit_events.iter() {
+        let Ok((mut decoration, transform, parent)) = decoration_query.get_mut(event.target) else {
+            continue;
+        };
+        let Some(harvest) = &map_state.decorations().get(decoration.kind).harvest else {
+            continue;
+        };
+
+        if let Some(tool) = &harvest.tool {
+            let has_tool = drops
+                .registry()
+                .find(tool)
+                .zip(player_query.get_single().ok())
+                .is_some_and(|(item, inventory)| inventory.count(item) > 0);
+            if !has_tool {
+                println!("Нужен инструмент: {}", tool);
+                continue;
+            }
+        }
+
+        commands.entity(parent.get()).insert(ChunkModified);
+        commands.entity(event.target).insert(HitAnimation(Timer::from_seconds(HIT_ANIMATION_TIME, TimerMode::Once)));
+        decoration.hits += 1;
+        if decoration.hits < harvest.durability {
+            continue;
+        }
+
+        let position = transform.translation().truncate();
+        for entry in &harvest.drops {
+            if rng.gen::<f32>() >= entry.chance {
+                continue;
+            }
+            let Some(item) = drops.registry().find(&entry.item) else {
+                println!("Неизвестный предмет в добыче: {}", entry.item);
+                continue;
+            };
+            let count = rng.gen_range(entry.min..=entry.max.max(entry.min));
+            if count == 0 {
+                continue;
+            }
+            let offset = Vec2::new(rng.gen_range(-0.4..0.4), rng.gen_range(-0.4..0.4)) * TILE_SIZE;
+            drops.spawn(&mut commands, ItemStack { item, count }, position + offset, DROP_PICKUP_DELAY);
+        }
+
+        match harvest.respawn_time {
+            Some(respawn_time) => {
+                decoration.hits = 0;
+                decoration.respawn_at = Some(clock.elapsed + respawn_time as f64);
+                commands
+                    .entity(event.target)
+                    .insert((Visibility::Hidden, Depleted))
+                    .remove::<Collider>();
+            }
+            None => commands.entity(event.target).despawn_recursive(),
+        }
+    }
+}
+
+// Добытые декорации вырастают снова, когда подойдёт время
+pub fn respawn_decorations(
+    mut commands: Commands,
+    clock: Res<WorldClock>,
+    map_state: Res<MapState>,
+    mut decoration_query: Query<(Entity, &mut Decoration, &mut Visibility, &Parent), With<Depleted>>,
+) {
+    for (entity, mut decoration, mut visibility, parent) in decoration_query.iter_mut() {
+        if decoration.respawn_at.is_some_and(|respawn_at| clock.elapsed < respawn_at) {
+            continue;
+        }
+
+        decoration.respawn_at = None;
+        *visibility = Visibility::Inherited;
+        let mut entity = commands.entity(entity);
+        entity.remove::<Depleted>();
+        if let Some(radius) = map_state.decorations().get(decoration.kind).collision_radius {
+            entity.insert(Collider { radius: radius * TILE_SIZE });
+        }
+        commands.entity(parent.get()).insert(ChunkModified);
+    }
+}
+
+// При ударе декорация быстро сплющивается и возвращается к своему размеру
+pub fn animate_hits(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut HitAnimation)>,
+) {
+    for (entity, mut transform, mut animation) in query.iter_mut() {
+        let squash = HIT_SQUASH * (animation.0.tick(time.delta()).percent() * PI).sin();
+        transform.scale = Vec3::new(1.0 + squash, 1.0 - squash, 1.0);
+
+        if animation.0.finished() {
+            transform.scale = Vec3::ONE;
+            commands.entity(entity).remove::<HitAnimation>();
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use bevy::prelude::*;
+use bevy::ecs::query::Has;
+use crate::game::map::ChunkSpawned;
+use crate::game::player::Player;
+use crate::game::tile_query::TileQuery;
+use crate::game::world::TilePos;
+
+// Прозрачность спрайта в моменты мигания во время неуязвимости
+const BLINK_ALPHA: f32 = 0.3;
+// Частота мигания, переключений в секунду
+const BLINK_RATE: f32 = 10.0;
+// Как далеко от начала мира искать сушу для точки появления, в тайлах
+const SPAWN_SEARCH_RADIUS: i32 = 24;
+
+#[derive(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    // Снимает здоровье, если удар прошёл сквозь неуязвимость. Возвращает true, если этот удар убил
+    pub fn damage(&mut self, amount: f32, invulnerability: Option<&mut Invulnerability>) -> bool {
+        // Уже убит другим ударом
+        if self.current <= 0.0 {
+            return false;
+        }
+        if let Some(invulnerability) = invulnerability {
+            if invulnerability.active() {
+                return false;
+            }
+            invulnerability.start();
+        }
+
+        self.current = (self.current - amount).max(0.0);
+        self.current <= 0.0
+    }
+}
+
+// Время после удара, в течение которого урон не проходит
+#[derive(Component)]
+pub struct Invulnerability {
+    duration: f32,
+    timer: Timer,
+}
+
+impl Invulnerability {
+    pub fn new(duration: f32) -> Self {
+        let mut timer = Timer::from_seconds(duration, TimerMode::Once);
+        timer.tick(timer.duration());
+        Self { duration, timer }
+    }
+
+    pub fn active(&self) -> bool {
+        !self.timer.finished()
+    }
+
+    pub fn start(&mut self) {
+        self.timer = Timer::from_seconds(self.duration, TimerMode::Once);
+    }
+}
+
+// Мёртвые не получают урон, а мёртвый игрок не управляется
+#[derive(Component)]
+pub struct Dead;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DamageSource {
+    Projectile,
+    Enemy,
+    // Холод, голод и прочие условия вокруг
+    Environment,
+}
+
+// Любой урон в игре проходит через это событие
+#[derive(Event)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    pub source: DamageSource,
+}
+
+#[derive(Event)]
+pub struct RespawnPlayer;
+
+// Где игрок появляется после смерти. Кровать, если она есть, важнее точки появления мира
+#[derive(Resource, Default)]
+pub struct SpawnPoint {
+    pub spawn: Vec2,
+    pub bed: Option<Vec2>,
+    // Точка появления мира уже перенесена на сушу
+    settled: bool,
+}
+
+impl SpawnPoint {
+    pub fn position(&self) -> Vec2 {
+        self.bed.unwrap_or(self.spawn)
+    }
+
+    // В новом мире точка появления снова в начале координат и ждёт проверки
+    pub fn reset(&mut self, bed: Option<Vec2>) {
+        *self = Self { bed, ..default() };
+    }
+}
+
+#[derive(Component)]
+pub struct HealthHud;
+
+pub fn setup_health(mut commands: Commands) {
+    commands.init_resource::<SpawnPoint>();
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 24.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            ..default()
+        }),
+        HealthHud,
+    ));
+}
+
+pub fn apply_damage(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageEvent>,
+    mut target_query: Query<(&mut Health, Option<&mut Invulnerability>, Has<Player>), Without<Dead>>,
+) {
+    for event in damage_events.iter() {
+        let Ok((mut health, mut invulnerability, is_player)) = target_query.get_mut(event.target) else {
+            continue;
+        };
+        // Урон от окружения идёт понемногу каждый кадр, неуязвимость его не останавливает
+        let invulnerability = invulnerability.as_deref_mut().filter(|_| event.source != DamageSource::Environment);
+        if !health.damage(event.amount, invulnerability) {
+            continue;
+        }
+
+        if is_player {
+            println!("Игрок погиб: {:?}", event.source);
+            commands.entity(event.target).insert(Dead);
+        } else {
+            commands.entity(event.target).despawn_recursive();
+        }
+    }
+}
+
+// Во время неуязвимости спрайт мигает
+pub fn tick_invulnerability(
+    time: Res<Time>,
+    mut query: Query<(&mut Invulnerability, Option<&mut Sprite>)>,
+) {
+    for (mut invulnerability, sprite) in query.iter_mut() {
+        invulnerability.timer.tick(time.delta());
+
+        if let Some(mut sprite) = sprite {
+            let hidden = invulnerability.active() && ((invulnerability.timer.elapsed_secs() * BLINK_RATE) as u32).is_multiple_of(2);
+            let alpha = if hidden { BLINK_ALPHA } else { 1.0 };
+            if sprite.color.a() != alpha {
+                sprite.color.set_a(alpha);
+            }
+        }
+    }
+}
+
+pub fn respawn_player(
+    mut commands: Commands,
+    mut respawn_events: EventReader<RespawnPlayer>,
+    spawn_point: Res<SpawnPoint>,
+    mut player_query: Query<(Entity, &mut Transform, &mut Health, Option<&mut Invulnerability>), (With<Player>, With<Dead>)>,
+) {
+    if respawn_events.is_empty() {
+        return;
+    }
+    respawn_events.clear();
+
+    let Ok((entity, mut transform, mut health, invulnerability)) = player_query.get_single_mut() else {
+        return;
+    };
+    let position = spawn_point.position();
+    transform.translation.x = position.x;
+    transform.translation.y = position.y;
+    health.current = health.max;
+    // Немного неуязвимости, чтобы не умереть сразу на точке появления
+    if let Some(mut invulnerability) = invulnerability {
+        invulnerability.start();
+    }
+    commands.entity(entity).remove::<Dead>();
+}
+
+// Ближайший к origin проходимый тайл в квадрате со стороной 2 * radius + 1.
+// walkable отвечает None для тайлов незагруженных чанков, и тогда ответа ещё нет:
+// ждём всю область, иначе результат зависел бы от порядка загрузки чанков
+fn nearest_walkable(origin: TilePos, radius: i32, walkable: impl Fn(TilePos) -> Option<bool>) -> Option<Option<TilePos>> {
+    let distance = |tile: TilePos| (tile.0 - origin.0).pow(2) + (tile.1 - origin.1).pow(2);
+    let mut nearest: Option<TilePos> = None;
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let position = origin.offset(dx, dy);
+            if walkable(position)? && nearest.is_none_or(|nearest| distance(position) < distance(nearest)) {
+                nearest = Some(position);
+            }
+        }
+    }
+    Some(nearest)
+}
+
+// Начало мира может оказаться в воде. Как только чанки вокруг него загрузятся, точка появления
+// переносится на ближайший проходимый тайл, а вместе с ней и игрок, если он стоит на исходной точке.
+// Область перепроверяется, только когда в неё попадает новый чанк. События читаются на кадр позже
+// отправки, когда сущности этих чанков уже созданы
+pub fn settle_spawn_point(
+    mut chunk_events: EventReader<ChunkSpawned>,
+    tiles: TileQuery,
+    mut spawn_point: ResMut<SpawnPoint>,
+    mut player_query: Query<&mut Transform, (With<Player>, Without<Dead>)>,
+) {
+    if spawn_point.settled {
+        return;
+    }
+
+    let origin = TilePos::from_world(spawn_point.spawn);
+    let min = origin.offset(-SPAWN_SEARCH_RADIUS, -SPAWN_SEARCH_RADIUS).chunk();
+    let max = origin.offset(SPAWN_SEARCH_RADIUS, SPAWN_SEARCH_RADIUS).chunk();
+    let in_area = |ChunkSpawned(chunk_pos): &ChunkSpawned| {
+        (min.0..=max.0).contains(&chunk_pos.0) && (min.1..=max.1).contains(&chunk_pos.1)
+    };
+    if !chunk_events.iter().any(in_area) {
+        return;
+    }
+
+    let walkable = |position: TilePos| tiles.tile(position).map(|tile| tile.walkable);
+    let Some(nearest) = nearest_walkable(origin, SPAWN_SEARCH_RADIUS, walkable) else {
+        return;
+    };
+    spawn_point.settled = true;
+
+    let Some(nearest) = nearest.filter(|nearest| *nearest != origin) else {
+        return;
+    };
+    let position = nearest.to_world();
+    println!("Точка появления перенесена на сушу: ({:.0}, {:.0})", position.x, position.y);
+    spawn_point.spawn = position;
+    for mut transform in player_query.iter_mut() {
+        if TilePos::from_world(transform.translation.truncate()) == origin {
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+    }
+}
+
+pub fn health_hud(
+    player_query: Query<&Health, With<Player>>,
+    mut hud_query: Query<&mut Text, With<HealthHud>>,
+) {
+    let (Ok(health), Ok(mut text)) = (player_query.get_single(), hud_query.get_single_mut()) else {
+        return;
+    };
+    text.sections[0].value = format!("Здоровье: {:.0}/{:.0}", health.current.ceil(), health.max);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invulnerability_blocks_hits_until_it_ends() {
+        let mut health = Health::new(100.0);
+        let mut invulnerability = Invulnerability::new(0.5);
+
+        health.damage(10.0, Some(&mut invulnerability));
+        assert_eq!(health.current, 90.0);
+        health.damage(10.0, Some(&mut invulnerability));
+        assert_eq!(health.current, 90.0, "удар во время неуязвимости не должен проходить");
+
+        invulnerability.timer.tick(std::time::Duration::from_secs_f32(0.5));
+        health.damage(10.0, Some(&mut invulnerability));
+        assert_eq!(health.current, 80.0);
+    }
+
+    #[test]
+    fn dies_at_zero_health() {
+        let mut health = Health::new(20.0);
+        assert!(!health.damage(15.0, None));
+        assert!(health.damage(15.0, None), "удар, доводящий здоровье до нуля, убивает");
+        assert_eq!(health.current, 0.0, "здоровье не уходит в минус");
+        assert!(!health.damage(15.0, None), "мёртвого нельзя убить второй раз");
+
+        let mut health = Health::new(20.0);
+        assert!(health.damage(20.0, None), "ровно 0 здоровья — это смерть");
+    }
+
+    #[test]
+    fn spawn_moves_to_nearest_land() {
+        let land = [TilePos(3, 0), TilePos(0, -4), TilePos(-5, -5)];
+        let walkable = |tile: TilePos| Some(land.contains(&tile));
+        assert_eq!(nearest_walkable(TilePos(0, 0), 8, walkable), Some(Some(TilePos(3, 0))));
+        assert_eq!(nearest_walkable(TilePos(0, 0), 2, walkable), Some(None), "суши в области нет");
+
+        let partly_loaded = |tile: TilePos| (tile.0 < 6).then(|| land.contains(&tile));
+        assert_eq!(nearest_walkable(TilePos(0, 0), 8, partly_loaded), None, "пока область не загружена, ответа нет");
+    }
+}
@@ -0,0 +1,468 @@
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use rand::Rng;
+use crate::game::health::Dead;
+use crate::game::item::{ItemRegistry, ItemStack, ItemType};
+use crate::game::map::{ChunkModified, MapState, YSort};
+use crate::game::menu::GameState;
+use crate::game::player::Player;
+use crate::game::station::StationPlacement;
+use crate::game::survival::Survival;
+use crate::game::world::{ChunkPosition, TILE_SIZE};
+
+pub const INVENTORY_SLOTS: usize = 36;
+// Первые слоты инвентаря видны на панели быстрого доступа
+pub const HOTBAR_SLOTS: usize = 9;
+pub const MAX_CARRY_WEIGHT: f32 = 50.0;
+const PICKUP_RADIUS: f32 = 24.0;
+// Выброшенный предмет какое-то время нельзя подобрать, иначе он сразу вернётся обратно
+const PICKUP_DELAY: f32 = 1.5;
+const WORLD_ITEM_SIZE: f32 = 20.0;
+
+#[derive(Component)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+    pub max_weight: f32,
+    // Выбранный слот панели быстрого доступа
+    pub selected: usize,
+}
+
+impl Inventory {
+    pub fn new(size: usize, max_weight: f32) -> Self {
+        Self {
+            slots: vec![None; size],
+            max_weight,
+            selected: 0,
+        }
+    }
+
+    pub fn slot(&self, index: usize) -> Option<ItemStack> {
+        self.slots.get(index).copied().flatten()
+    }
+
+    pub fn selected_stack(&self) -> Option<ItemStack> {
+        self.slot(self.selected)
+    }
+
+    pub fn weight(&self, registry: &ItemRegistry) -> f32 {
+        self.slots
+            .iter()
+            .flatten()
+            .map(|stack| registry.get(stack.item).weight * stack.count as f32)
+            .sum()
+    }
+
+    pub fn count(&self, item: ItemType) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|stack| stack.item == item)
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    // Кладёт сколько влезет по весу и по слотам: сначала в неполные стопки, потом в пустые слоты.
+    // Возвращает, сколько не поместилось
+    pub fn add(&mut self, registry: &ItemRegistry, stack: ItemStack) -> u32 {
+        let definition = registry.get(stack.item);
+        let mut left = stack.count;
+        if definition.weight > 0.0 {
+            let free_weight = (self.max_weight - self.weight(registry)).max(0.0);
+            left = left.min((free_weight / definition.weight + 1e-4).floor() as u32);
+        }
+        let rejected = stack.count - left;
+
+        for slot in self.slots.iter_mut().flatten().filter(|slot| slot.item == stack.item) {
+            let moved = left.min(definition.max_stack.saturating_sub(slot.count));
+            slot.count += moved;
+            left -= moved;
+        }
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            if left == 0 {
+                break;
+            }
+            let moved = left.min(definition.max_stack);
+            *slot = Some(ItemStack { item: stack.item, count: moved });
+            left -= moved;
+        }
+
+        rejected + left
+    }
+
+    // Забирает предметы только если их хватает, начиная с последних слотов
+    pub fn remove(&mut self, item: ItemType, count: u32) -> bool {
+        if self.count(item) < count {
+            return false;
+        }
+
+        let mut left = count;
+        for slot in self.slots.iter_mut().rev() {
+            let Some(stack) = slot.as_mut().filter(|stack| stack.item == item) else {
+                continue;
+            };
+            let taken = left.min(stack.count);
+            stack.count -= taken;
+            left -= taken;
+            if stack.count == 0 {
+                *slot = None;
+            }
+            if left == 0 {
+                break;
+            }
+        }
+        true
+    }
+
+    // Вынимает из слота до count предметов
+    pub fn take(&mut self, index: usize, count: u32) -> Option<ItemStack> {
+        let slot = self.slots.get_mut(index)?;
+        let stack = slot.as_mut()?;
+        let taken = count.min(stack.count);
+        stack.count -= taken;
+        let item = stack.item;
+        if stack.count == 0 {
+            *slot = None;
+        }
+        (taken > 0).then_some(ItemStack { item, count: taken })
+    }
+
+    // Перекладывает count предметов из слота в слот. Одинаковые предметы складываются в стопку,
+    // разные меняются местами, если перекладывается вся стопка
+    pub fn transfer(&mut self, registry: &ItemRegistry, from: usize, to: usize, count: u32) {
+        if from == to || to >= self.slots.len() {
+            return;
+        }
+        let Some(source) = self.slot(from) else {
+            return;
+        };
+        let count = count.min(source.count);
+
+        match self.slot(to) {
+            None => {
+                self.take(from, count);
+                self.slots[to] = Some(ItemStack { item: source.item, count });
+            }
+            Some(mut target) if target.item == source.item => {
+                let moved = count.min(registry.get(target.item).max_stack.saturating_sub(target.count));
+                self.take(from, moved);
+                target.count += moved;
+                self.slots[to] = Some(target);
+            }
+            Some(_) if count == source.count => self.slots.swap(from, to),
+            Some(_) => {}
+        }
+    }
+
+    // Для сохранения предметы записываются по имени
+    pub fn to_save(&self, registry: &ItemRegistry) -> Vec<Option<(String, u32)>> {
+        self.slots
+            .iter()
+            .map(|slot| slot.map(|stack| (registry.get(stack.item).name.clone(), stack.count)))
+            .collect()
+    }
+
+    pub fn load_save(&mut self, registry: &ItemRegistry, saved: &[Option<(String, u32)>]) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+
+        for (slot, saved) in self.slots.iter_mut().zip(saved) {
+            let Some((name, count)) = saved else {
+                continue;
+            };
+            match registry.find(name) {
+                Some(item) => *slot = Some(ItemStack { item, count: *count }),
+                None => println!("Неизвестный предмет в сохранении: {}", name),
+            }
+        }
+    }
+}
+
+// Предмет, лежащий на земле
+#[derive(Component)]
+pub struct WorldItem {
+    pub stack: ItemStack,
+    pickup_delay: Timer,
+}
+
+// Выбросить предметы из слота инвентаря игрока
+#[derive(Event)]
+pub struct DropItem {
+    pub slot: usize,
+    pub count: u32,
+}
+
+// Предметы, которые игрок не выбрасывал только что сам, можно подобрать сразу
+pub const IMMEDIATE_PICKUP: f32 = 0.0;
+
+pub fn spawn_world_item(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    registry: &ItemRegistry,
+    stack: ItemStack,
+    position: Vec2,
+    pickup_delay: f32,
+) -> Entity {
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_xyz(position.x, position.y, 1.0),
+            texture: asset_server.load(registry.get(stack.item).icon.as_str()),
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(WORLD_ITEM_SIZE)),
+                ..default()
+            },
+            ..default()
+        },
+        WorldItem {
+            stack,
+            pickup_delay: Timer::from_seconds(pickup_delay, TimerMode::Once),
+        },
+        YSort,
+    ))
+    .id()
+}
+
+// Выброшенный предмет принадлежит чанку, на который упал, и сохраняется вместе с ним.
+// Предмет за пределами загруженных чанков сохранить некуда, он пропадает
+pub fn update_item_chunks(
+    mut commands: Commands,
+    map_state: Res<MapState>,
+    item_query: Query<(Entity, &Transform), (With<WorldItem>, Without<Parent>)>,
+) {
+    for (entity, transform) in item_query.iter() {
+        match map_state.chunk_entity(ChunkPosition::from_world(transform.translation.truncate())) {
+            Some(chunk) => {
+                commands.entity(entity).set_parent(chunk);
+                commands.entity(chunk).insert(ChunkModified);
+            }
+            None => commands.entity(entity).despawn(),
+        }
+    }
+}
+
+// Всё, что нужно, чтобы выбросить предметы в мир из системы
+#[derive(SystemParam)]
+pub struct ItemDrops<'w> {
+    asset_server: Res<'w, AssetServer>,
+    registry: Res<'w, ItemRegistry>,
+}
+
+impl ItemDrops<'_> {
+    pub fn registry(&self) -> &ItemRegistry {
+        &self.registry
+    }
+
+    pub fn spawn(&self, commands: &mut Commands, stack: ItemStack, position: Vec2, pickup_delay: f32) {
+        spawn_world_item(commands, &self.asset_server, &self.registry, stack, position, pickup_delay);
+    }
+}
+
+pub fn pickup_items(
+    mut commands: Commands,
+    time: Res<Time>,
+    registry: Res<ItemRegistry>,
+    mut player_query: Query<(&Transform, &mut Inventory), (With<Player>, Without<Dead>)>,
+    mut item_query: Query<(Entity, &Transform, &mut WorldItem, Option<&Parent>), Without<Player>>,
+) {
+    let Ok((player_transform, mut inventory)) = player_query.get_single_mut() else {
+        return;
+    };
+    let position = player_transform.translation.truncate();
+
+    for (entity, transform, mut world_item, parent) in item_query.iter_mut() {
+        if !world_item.pickup_delay.tick(time.delta()).finished() {
+            continue;
+        }
+        if transform.translation.truncate().distance(position) > PICKUP_RADIUS {
+            continue;
+        }
+
+        let left = inventory.add(&registry, world_item.stack);
+        if left == world_item.stack.count {
+            continue;
+        }
+        if left == 0 {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            world_item.stack.count = left;
+        }
+        if let Some(parent) = parent {
+            commands.entity(parent.get()).insert(ChunkModified);
+        }
+    }
+}
+
+// G выбрасывает один предмет из выбранного слота, остальное приходит событиями из интерфейса
+pub fn drop_items(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    registry: Res<ItemRegistry>,
+    mut drop_events: EventReader<DropItem>,
+    mut player_query: Query<(&Transform, &mut Inventory), (With<Player>, Without<Dead>)>,
+) {
+    let Ok((transform, mut inventory)) = player_query.get_single_mut() else {
+        drop_events.clear();
+        return;
+    };
+
+    let mut drops: Vec<(usize, u32)> = drop_events.iter().map(|event| (event.slot, event.count)).collect();
+    if keyboard.just_pressed(KeyCode::G) {
+        drops.push((inventory.selected, 1));
+    }
+
+    let mut rng = rand::thread_rng();
+    for (slot, count) in drops {
+        let Some(stack) = inventory.take(slot, count) else {
+            continue;
+        };
+        let offset = Vec2::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5)) * TILE_SIZE;
+        spawn_world_item(
+            &mut commands,
+            &asset_server,
+            &registry,
+            stack,
+            transform.translation.truncate() + offset,
+            PICKUP_DELAY,
+        );
+    }
+}
+
+// F применяет предмет из выбранного слота: еду съедает, станцию ставит под курсор
+pub fn use_item(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    registry: Res<ItemRegistry>,
+    placement: StationPlacement,
+    mut player_query: Query<(&Transform, &mut Inventory, &mut Survival), (With<Player>, Without<Dead>)>,
+) {
+    if game_state.paused || !keyboard.just_pressed(KeyCode::F) {
+        return;
+    }
+    let Ok((transform, mut inventory, mut survival)) = player_query.get_single_mut() else {
+        return;
+    };
+    let Some(item) = inventory.selected_stack().map(|stack| stack.item) else {
+        return;
+    };
+
+    if let Some(food) = registry.get(item).food {
+        let selected = inventory.selected;
+        inventory.take(selected, 1);
+        survival.eat(food);
+    } else if let Some(kind) = placement.station(item) {
+        placement.place(&mut commands, kind, &mut inventory, transform.translation.truncate());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::item::test_item;
+
+    // Предметы сортируются по имени: feather 0, stone 1, wood 2
+    fn registry() -> ItemRegistry {
+        ItemRegistry::from_definitions(vec![test_item("wood", 10, 1.0), test_item("stone", 5, 4.0), test_item("feather", 3, 0.0)]).unwrap()
+    }
+
+    fn stack(registry: &ItemRegistry, name: &str, count: u32) -> ItemStack {
+        ItemStack { item: registry.find(name).unwrap(), count }
+    }
+
+    #[test]
+    fn add_fills_partial_stacks_before_empty_slots() {
+        let registry = registry();
+        let mut inventory = Inventory::new(3, 100.0);
+        assert_eq!(inventory.add(&registry, stack(&registry, "wood", 4)), 0);
+        assert_eq!(inventory.add(&registry, stack(&registry, "wood", 9)), 0);
+
+        assert_eq!(inventory.slot(0), Some(stack(&registry, "wood", 10)));
+        assert_eq!(inventory.slot(1), Some(stack(&registry, "wood", 3)));
+        assert_eq!(inventory.slot(2), None);
+    }
+
+    #[test]
+    fn add_returns_what_does_not_fit_in_slots() {
+        let registry = registry();
+        let mut inventory = Inventory::new(2, 100.0);
+        assert_eq!(inventory.add(&registry, stack(&registry, "feather", 8)), 2);
+        assert_eq!(inventory.count(registry.find("feather").unwrap()), 6);
+    }
+
+    #[test]
+    fn add_stops_at_weight_limit() {
+        let registry = registry();
+        let mut inventory = Inventory::new(INVENTORY_SLOTS, 10.0);
+        assert_eq!(inventory.add(&registry, stack(&registry, "wood", 3)), 0);
+        // Осталось 7 веса, камень весит 4
+        assert_eq!(inventory.add(&registry, stack(&registry, "stone", 3)), 2);
+        assert_eq!(inventory.weight(&registry), 7.0);
+        // Невесомые предметы ограничены только слотами
+        assert_eq!(inventory.add(&registry, stack(&registry, "feather", 3)), 0);
+    }
+
+    #[test]
+    fn take_and_remove() {
+        let registry = registry();
+        let mut inventory = Inventory::new(4, 100.0);
+        inventory.add(&registry, stack(&registry, "wood", 15));
+
+        assert_eq!(inventory.take(1, 10), Some(stack(&registry, "wood", 5)));
+        assert_eq!(inventory.slot(1), None);
+        assert_eq!(inventory.take(1, 1), None);
+
+        let wood = registry.find("wood").unwrap();
+        assert!(!inventory.remove(wood, 11));
+        assert!(inventory.remove(wood, 4));
+        assert_eq!(inventory.slot(0), Some(stack(&registry, "wood", 6)));
+    }
+
+    #[test]
+    fn transfer_merges_and_swaps() {
+        let registry = registry();
+        let mut inventory = Inventory::new(4, 100.0);
+        inventory.slots[0] = Some(stack(&registry, "wood", 8));
+        inventory.slots[1] = Some(stack(&registry, "wood", 5));
+        inventory.slots[2] = Some(stack(&registry, "stone", 2));
+
+        // В стопку влезает только до max_stack
+        inventory.transfer(&registry, 1, 0, 5);
+        assert_eq!(inventory.slot(0), Some(stack(&registry, "wood", 10)));
+        assert_eq!(inventory.slot(1), Some(stack(&registry, "wood", 3)));
+
+        // Разные предметы меняются местами только целой стопкой
+        inventory.transfer(&registry, 2, 1, 1);
+        assert_eq!(inventory.slot(1), Some(stack(&registry, "wood", 3)));
+        inventory.transfer(&registry, 2, 1, 2);
+        assert_eq!(inventory.slot(1), Some(stack(&registry, "stone", 2)));
+        assert_eq!(inventory.slot(2), Some(stack(&registry, "wood", 3)));
+
+        inventory.transfer(&registry, 2, 3, 1);
+        assert_eq!(inventory.slot(2), Some(stack(&registry, "wood", 2)));
+        assert_eq!(inventory.slot(3), Some(stack(&registry, "wood", 1)));
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let registry = registry();
+        let mut inventory = Inventory::new(4, 100.0);
+        inventory.slots[0] = Some(stack(&registry, "stone", 2));
+        inventory.slots[2] = Some(stack(&registry, "feather", 3));
+
+        let saved = inventory.to_save(&registry);
+        assert_eq!(saved, [Some(("stone".to_string(), 2)), None, Some(("feather".to_string(), 3)), None]);
+
+        let mut loaded = Inventory::new(4, 100.0);
+        loaded.slots[1] = Some(stack(&registry, "wood", 1));
+        loaded.load_save(&registry, &saved);
+        assert_eq!(loaded.slots, inventory.slots);
+    }
+
+    // Предметы, которых больше нет в реестре, пропадают, остальные остаются в своих слотах
+    #[test]
+    fn load_save_skips_unknown_items() {
+        let registry = registry();
+        let mut inventory = Inventory::new(3, 100.0);
+        inventory.load_save(&registry, &[Some(("iron".to_string(), 1)), Some(("wood".to_string(), 4))]);
+        assert_eq!(inventory.slots, [None, Some(stack(&registry, "wood", 4)), None]);
+    }
+}
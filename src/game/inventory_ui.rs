@@ -0,0 +1,454 @@
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use bevy::window::PrimaryWindow;
+use crate::game::health::Dead;
+use crate::game::inventory::{DropItem, Inventory, HOTBAR_SLOTS, INVENTORY_SLOTS};
+use crate::game::item::ItemRegistry;
+use crate::game::menu::GameState;
+use crate::game::player::Player;
+
+const SLOT_SIZE: f32 = 48.0;
+const SLOT_GAP: f32 = 4.0;
+const ICON_SIZE: f32 = 32.0;
+const SLOT_COLOR: Color = Color::rgba(0.15, 0.15, 0.15, 0.8);
+const SELECTED_SLOT_COLOR: Color = Color::rgba(0.45, 0.45, 0.3, 0.9);
+
+#[derive(Clone, Copy)]
+struct Drag {
+    from: usize,
+    count: u32,
+    // Левая кнопка тащит всю стопку, правая — половину
+    button: MouseButton,
+}
+
+#[derive(Resource, Default)]
+pub struct InventoryUi {
+    pub open: bool,
+    drag: Option<Drag>,
+}
+
+// Ячейка интерфейса, показывающая слот инвентаря игрока.
+// Первые слоты показаны дважды: на панели быстрого доступа и в окне инвентаря
+#[derive(Component)]
+pub struct SlotUi {
+    index: usize,
+    hotbar: bool,
+}
+
+#[derive(Component)]
+pub struct SlotIcon;
+
+#[derive(Component)]
+pub struct SlotCount;
+
+#[derive(Component)]
+pub struct InventoryWindow;
+
+#[derive(Component)]
+pub struct InventoryPanel;
+
+#[derive(Component)]
+pub struct InventoryWeight;
+
+#[derive(Component)]
+pub struct Tooltip;
+
+#[derive(Component)]
+pub struct DragIcon;
+
+// Что находится под курсором. Попадание считается по прямоугольникам узлов интерфейса
+#[derive(SystemParam)]
+pub struct SlotPointer<'w, 's> {
+    window_query: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    slot_query: Query<'w, 's, (&'static SlotUi, &'static Node, &'static GlobalTransform)>,
+    panel_query: Query<'w, 's, (&'static Node, &'static GlobalTransform), With<InventoryPanel>>,
+}
+
+impl SlotPointer<'_, '_> {
+    pub fn cursor(&self) -> Option<Vec2> {
+        self.window_query.get_single().ok()?.cursor_position()
+    }
+
+    // Слоты окна инвентаря учитываются, только пока окно открыто
+    pub fn slot(&self, open: bool) -> Option<usize> {
+        let cursor = self.cursor()?;
+        self.slot_query
+            .iter()
+            .find(|(slot, node, transform)| (open || slot.hotbar) && node_contains(node, transform, cursor))
+            .map(|(slot, _, _)| slot.index)
+    }
+
+    pub fn over_panel(&self, open: bool) -> bool {
+        let Some(cursor) = self.cursor() else {
+            return false;
+        };
+        open && self.panel_query.iter().any(|(node, transform)| node_contains(node, transform, cursor))
+    }
+}
+
+fn node_contains(node: &Node, transform: &GlobalTransform, point: Vec2) -> bool {
+    Rect::from_center_size(transform.translation().truncate(), node.size()).contains(point)
+}
+
+fn display(visible: bool) -> Display {
+    if visible {
+        Display::Flex
+    } else {
+        Display::None
+    }
+}
+
+pub fn setup_inventory_ui(mut commands: Commands) {
+    commands.init_resource::<InventoryUi>();
+
+    // Панель быстрого доступа по центру внизу экрана
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                bottom: Val::Px(10.0),
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(SLOT_GAP),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for index in 0..HOTBAR_SLOTS {
+                spawn_slot(parent, index, true);
+            }
+        });
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    display: Display::None,
+                    ..default()
+                },
+                ..default()
+            },
+            InventoryWindow,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Px(12.0)),
+                            row_gap: Val::Px(8.0),
+                            ..default()
+                        },
+                        background_color: Color::rgba(0.1, 0.1, 0.1, 0.9).into(),
+                        ..default()
+                    },
+                    InventoryPanel,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Инвентарь",
+                        TextStyle {
+                            font_size: 30.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 18.0,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ),
+                        InventoryWeight,
+                    ));
+
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Px(HOTBAR_SLOTS as f32 * (SLOT_SIZE + SLOT_GAP)),
+                                flex_wrap: FlexWrap::Wrap,
+                                column_gap: Val::Px(SLOT_GAP),
+                                row_gap: Val::Px(SLOT_GAP),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for index in 0..INVENTORY_SLOTS {
+                                spawn_slot(parent, index, false);
+                            }
+                        });
+                });
+        });
+
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                padding: UiRect::all(Val::Px(6.0)),
+                display: Display::None,
+                ..default()
+            },
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.85).into(),
+            z_index: ZIndex::Global(10),
+            ..default()
+        },
+        Tooltip,
+    ));
+
+    commands.spawn((
+        ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Px(ICON_SIZE),
+                height: Val::Px(ICON_SIZE),
+                display: Display::None,
+                ..default()
+            },
+            z_index: ZIndex::Global(11),
+            ..default()
+        },
+        DragIcon,
+    ));
+}
+
+fn spawn_slot(parent: &mut ChildBuilder, index: usize, hotbar: bool) {
+    parent
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Px(SLOT_SIZE),
+                    height: Val::Px(SLOT_SIZE),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: SLOT_COLOR.into(),
+                ..default()
+            },
+            SlotUi { index, hotbar },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                ImageBundle {
+                    style: Style {
+                        width: Val::Px(ICON_SIZE),
+                        height: Val::Px(ICON_SIZE),
+                        display: Display::None,
+                        ..default()
+                    },
+                    ..default()
+                },
+                SlotIcon,
+            ));
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 14.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(3.0),
+                    bottom: Val::Px(1.0),
+                    ..default()
+                }),
+                SlotCount,
+            ));
+        });
+}
+
+pub fn toggle_inventory(
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    mut ui: ResMut<InventoryUi>,
+    mut window_query: Query<&mut Style, With<InventoryWindow>>,
+) {
+    if game_state.paused || !(keyboard.just_pressed(KeyCode::I) || keyboard.just_pressed(KeyCode::Tab)) {
+        return;
+    }
+
+    ui.open = !ui.open;
+    ui.drag = None;
+    for mut style in window_query.iter_mut() {
+        style.display = display(ui.open);
+    }
+}
+
+// Клик по слоту панели выбирает его. Стопку можно перетащить в другой слот,
+// правой кнопкой — только половину, а брошенная мимо окна стопка выпадает на землю
+pub fn inventory_mouse(
+    mouse: Res<Input<MouseButton>>,
+    pointer: SlotPointer,
+    mut ui: ResMut<InventoryUi>,
+    mut game_state: ResMut<GameState>,
+    registry: Res<ItemRegistry>,
+    mut drop_events: EventWriter<DropItem>,
+    mut player_query: Query<&mut Inventory, (With<Player>, Without<Dead>)>,
+) {
+    let hovered = pointer.slot(ui.open);
+    game_state.pointer_over_ui = ui.open || hovered.is_some() || ui.drag.is_some();
+
+    let Ok(mut inventory) = player_query.get_single_mut() else {
+        ui.drag = None;
+        return;
+    };
+    if game_state.paused {
+        ui.drag = None;
+        return;
+    }
+
+    let Some(drag) = ui.drag else {
+        let Some(index) = hovered else {
+            return;
+        };
+        let button = if mouse.just_pressed(MouseButton::Left) {
+            MouseButton::Left
+        } else if mouse.just_pressed(MouseButton::Right) {
+            MouseButton::Right
+        } else {
+            return;
+        };
+
+        if button == MouseButton::Left && index < HOTBAR_SLOTS && inventory.selected != index {
+            inventory.selected = index;
+        }
+        if let Some(stack) = inventory.slot(index) {
+            let count = if button == MouseButton::Left { stack.count } else { stack.count.div_ceil(2) };
+            ui.drag = Some(Drag { from: index, count, button });
+        }
+        return;
+    };
+
+    if !mouse.just_released(drag.button) {
+        return;
+    }
+    ui.drag = None;
+    match hovered {
+        Some(to) => inventory.transfer(&registry, drag.from, to, drag.count),
+        None if !pointer.over_panel(ui.open) => drop_events.send(DropItem {
+            slot: drag.from,
+            count: drag.count,
+        }),
+        None => {}
+    }
+}
+
+pub fn update_slots(
+    registry: Res<ItemRegistry>,
+    asset_server: Res<AssetServer>,
+    player_query: Query<&Inventory, (With<Player>, Changed<Inventory>)>,
+    mut slot_query: Query<(&SlotUi, &Children, &mut BackgroundColor)>,
+    mut icon_query: Query<(&mut UiImage, &mut Style), With<SlotIcon>>,
+    mut count_query: Query<&mut Text, (With<SlotCount>, Without<InventoryWeight>)>,
+    mut weight_query: Query<&mut Text, (With<InventoryWeight>, Without<SlotCount>)>,
+) {
+    let Ok(inventory) = player_query.get_single() else {
+        return;
+    };
+
+    for (slot, children, mut background) in slot_query.iter_mut() {
+        let stack = inventory.slot(slot.index);
+        let selected = slot.hotbar && slot.index == inventory.selected;
+        *background = if selected { SELECTED_SLOT_COLOR } else { SLOT_COLOR }.into();
+
+        for &child in children.iter() {
+            if let Ok((mut image, mut style)) = icon_query.get_mut(child) {
+                style.display = display(stack.is_some());
+                if let Some(stack) = stack {
+                    image.texture = asset_server.load(registry.get(stack.item).icon.as_str());
+                }
+            }
+            if let Ok(mut text) = count_query.get_mut(child) {
+                text.sections[0].value = match stack {
+                    Some(stack) if stack.count > 1 => stack.count.to_string(),
+                    _ => String::new(),
+                };
+            }
+        }
+    }
+
+    for mut text in weight_query.iter_mut() {
+        text.sections[0].value = format!("Вес: {:.1}/{:.0}", inventory.weight(&registry), inventory.max_weight);
+    }
+}
+
+// Подсказка над слотом под курсором и иконка перетаскиваемой стопки
+pub fn update_pointer_ui(
+    pointer: SlotPointer,
+    ui: Res<InventoryUi>,
+    registry: Res<ItemRegistry>,
+    asset_server: Res<AssetServer>,
+    player_query: Query<&Inventory, With<Player>>,
+    mut tooltip_query: Query<(&mut Style, &mut Text), (With<Tooltip>, Without<DragIcon>)>,
+    mut drag_query: Query<(&mut Style, &mut UiImage), (With<DragIcon>, Without<Tooltip>)>,
+) {
+    let (Ok((mut tooltip_style, mut tooltip_text)), Ok((mut drag_style, mut drag_image))) =
+        (tooltip_query.get_single_mut(), drag_query.get_single_mut())
+    else {
+        return;
+    };
+    let cursor = pointer.cursor();
+    let inventory = player_query.get_single().ok();
+
+    let dragged = ui.drag.zip(inventory).and_then(|(drag, inventory)| inventory.slot(drag.from));
+    match (dragged, cursor) {
+        (Some(stack), Some(cursor)) => {
+            drag_image.texture = asset_server.load(registry.get(stack.item).icon.as_str());
+            drag_style.left = Val::Px(cursor.x - ICON_SIZE * 0.5);
+            drag_style.top = Val::Px(cursor.y - ICON_SIZE * 0.5);
+            drag_style.display = Display::Flex;
+        }
+        _ => {
+            if drag_style.display != Display::None {
+                drag_style.display = Display::None;
+            }
+        }
+    }
+
+    let hovered = pointer
+        .slot(ui.open)
+        .zip(inventory)
+        .and_then(|(index, inventory)| inventory.slot(index))
+        .filter(|_| ui.drag.is_none());
+    match (hovered, cursor) {
+        (Some(stack), Some(cursor)) => {
+            let definition = registry.get(stack.item);
+            let mut value = format!("{}\n{}\nВес: {:.1}", definition.title, definition.description, definition.weight);
+            if let Some(food) = definition.food {
+                value.push_str(&format!("\nСытость: +{:.0}", food));
+            }
+            tooltip_text.sections[0].value = value;
+            tooltip_style.left = Val::Px(cursor.x + 16.0);
+            tooltip_style.top = Val::Px(cursor.y + 16.0);
+            tooltip_style.display = Display::Flex;
+        }
+        _ => {
+            if tooltip_style.display != Display::None {
+                tooltip_style.display = Display::None;
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::path::Path;
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::game::data::{assets_dir, load_ron_dir};
+
+// Индекс предмета в реестре, предметы сортируются по имени.
+// В сохранения пишется имя, индекс может измениться при добавлении предметов
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ItemType(pub u16);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ItemDefinition {
+    pub name: String,
+    // Название для интерфейса
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub icon: String,
+    pub max_stack: u32,
+    // Вес одного предмета
+    pub weight: f32,
+    // Сколько сытости восстанавливает, если предмет съедобный
+    #[serde(default)]
+    pub food: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemStack {
+    pub item: ItemType,
+    pub count: u32,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct ItemRegistry {
+    items: Vec<ItemDefinition>,
+}
+
+impl ItemRegistry {
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        Self::from_definitions(load_ron_dir(dir)?)
+    }
+
+    pub fn from_definitions(mut items: Vec<ItemDefinition>) -> Result<Self, String> {
+        if items.len() > u16::MAX as usize {
+            return Err(format!("слишком много предметов: {}", items.len()));
+        }
+        items.sort_by(|a, b| a.name.cmp(&b.name));
+
+        if let Some(pair) = items.windows(2).find(|pair| pair[0].name == pair[1].name) {
+            return Err(format!("предмет {} объявлен дважды", pair[0].name));
+        }
+        if let Some(item) = items.iter().find(|item| item.max_stack == 0 || item.weight < 0.0) {
+            return Err(format!("предмет {}: размер стопки должен быть больше нуля, а вес не меньше нуля", item.name));
+        }
+
+        Ok(Self { items })
+    }
+
+    pub fn get(&self, item: ItemType) -> &ItemDefinition {
+        &self.items[item.0 as usize]
+    }
+
+    pub fn find(&self, name: &str) -> Option<ItemType> {
+        self.items
+            .binary_search_by(|item| item.name.as_str().cmp(name))
+            .ok()
+            .map(|index| ItemType(index as u16))
+    }
+}
+
+pub fn setup_items(mut commands: Commands) {
+    let registry = ItemRegistry::load(&assets_dir().join("items"))
+        .unwrap_or_else(|err| panic!("Не удалось загрузить предметы: {}", err));
+    commands.insert_resource(registry);
+}
+
+// Простое определение предмета для тестов инвентаря, крафта и построек
+#[cfg(test)]
+pub fn test_item(name: &str, max_stack: u32, weight: f32) -> ItemDefinition {
+    ItemDefinition {
+        name: name.to_string(),
+        title: name.to_string(),
+        description: String::new(),
+        icon: String::new(),
+        max_stack,
+        weight,
+        food: None,
+    }
+}
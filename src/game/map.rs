@@ -1,127 +1,634 @@
 use bevy::prelude::*;
+use bevy::ecs::query::Has;
+use bevy::ecs::system::SystemParam;
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+use serde::Deserialize;
 use std::collections::HashMap;
-use crate::game::generate_map::{ChunkPosition, generate_chunk, BiomeType, TileType};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::game::biome::BiomeRegistry;
+use crate::game::building::{spawn_structure, Structure};
+use crate::game::chunk_store::{ChunkStore, StoredChunk};
+use crate::game::collision::Collider;
+use crate::game::data::{assets_dir, load_ron, DirWatcher};
+use crate::game::decoration::{DecorationRegistry, DecorationType};
+use crate::game::generate_map::{ChunkData, DroppedItem, PlacedStation, generate_chunk};
+use crate::game::harvest::Depleted;
+use crate::game::health::Health;
+use crate::game::inventory::{spawn_world_item, WorldItem, IMMEDIATE_PICKUP};
+use crate::game::item::{ItemRegistry, ItemStack};
+use crate::game::station::{spawn_station, CraftingStation, StationRegistry};
+use crate::game::structure::StructureRegistry;
+use crate::game::terrain::{TerrainGenerator, TerrainSettings};
+use crate::game::tilemap::{empty_chunk_mesh, ChunkTiles, TileAtlas};
+use crate::game::world::{ChunkPosition, TilePos, CHUNK_WORLD_SIZE, TILE_SIZE};
 
 #[derive(Resource)]
 pub struct MapState {
     loaded_chunks: HashMap<ChunkPosition, Entity>,
-    seed: u64,
+    // Чанки, которые сейчас генерируются в фоне
+    pending_chunks: HashMap<ChunkPosition, Task<ChunkData>>,
+    // Готовые чанки, которые ещё ждут своей очереди на спавн
+    ready_chunks: HashMap<ChunkPosition, ChunkData>,
+    generator: Arc<TerrainGenerator>,
+    biomes: Arc<BiomeRegistry>,
+    decorations: DecorationRegistry,
+    structures: StructureRegistry,
+    write_timer: Timer,
 }
 
-const CHUNK_SIZE: i32 = 16;
-const RENDER_DISTANCE: i32 = 2;
+impl MapState {
+    pub fn biomes(&self) -> &BiomeRegistry {
+        &self.biomes
+    }
+
+    pub fn decorations(&self) -> &DecorationRegistry {
+        &self.decorations
+    }
+
+    pub fn structures(&self) -> &StructureRegistry {
+        &self.structures
+    }
+
+    pub fn chunk_entity(&self, chunk_pos: ChunkPosition) -> Option<Entity> {
+        self.loaded_chunks.get(&chunk_pos).copied()
+    }
+
+    pub fn loaded_chunks(&self) -> impl Iterator<Item = (ChunkPosition, Entity)> + '_ {
+        self.loaded_chunks.iter().map(|(chunk_pos, entity)| (*chunk_pos, *entity))
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.generator.seed()
+    }
+
+    // Переключает карту на другой мир: все чанки выгружаются без сохранения
+    pub fn reset(&mut self, commands: &mut Commands, seed: u64) {
+        for (_, entity) in self.loaded_chunks.drain() {
+            commands.entity(entity).despawn_recursive();
+        }
+        self.pending_chunks.clear();
+        self.ready_chunks.clear();
+        self.generator = Arc::new(TerrainGenerator::new(seed, self.generator.settings().clone()));
+    }
+}
+
+// Следит за assets/biomes, assets/decorations и assets/structures, чтобы подхватывать правки без перезапуска
+#[derive(Resource)]
+pub struct WorldDataWatcher {
+    biomes: DirWatcher,
+    decorations: DirWatcher,
+    structures: DirWatcher,
+}
+
+#[derive(Component)]
+pub struct Decoration {
+    pub kind: DecorationType,
+    pub hits: u32,
+    pub respawn_at: Option<f64>,
+}
+
+// Чанк менялся после генерации и при выгрузке сохраняется в ChunkStore.
+// Ставится системами, которые меняют тайлы, декорации, постройки, станции или предметы на земле чанка
+#[derive(Component)]
+pub struct ChunkModified;
+
+// Хранилище изменённых чанков вместе с запросами, которые нужны, чтобы собрать чанк обратно в ChunkData
+#[derive(SystemParam)]
+pub struct ChunkPersistence<'w, 's> {
+    store: ResMut<'w, ChunkStore>,
+    chunk_query: Query<'w, 's, (&'static ChunkTiles, Option<&'static Children>), With<ChunkModified>>,
+    decoration_query: Query<'w, 's, (&'static Decoration, &'static Transform)>,
+    structure_query: Query<'w, 's, (&'static Structure, &'static Transform, &'static Health)>,
+    items: Res<'w, ItemRegistry>,
+    item_query: Query<'w, 's, (&'static WorldItem, &'static Transform)>,
+    stations: Res<'w, StationRegistry>,
+    station_query: Query<'w, 's, (&'static CraftingStation, &'static Transform)>,
+}
+
+// Всё, что нужно, чтобы заспавнить содержимое чанка. Предметы и станции лежат в чанке по имени
+#[derive(SystemParam)]
+pub struct ChunkContents<'w> {
+    asset_server: Res<'w, AssetServer>,
+    items: Res<'w, ItemRegistry>,
+    stations: Res<'w, StationRegistry>,
+}
+
+impl ChunkPersistence<'_, '_> {
+    // Сохраняет чанк в хранилище, если он менялся после генерации
+    fn store_chunk(&mut self, chunk_pos: ChunkPosition, entity: Entity, map_state: &MapState) {
+        let Ok((chunk_tiles, children)) = self.chunk_query.get(entity) else {
+            return;
+        };
+
+        let decorations = children
+            .into_iter()
+            .flatten()
+            .filter_map(|child| self.decoration_query.get(*child).ok())
+            .map(|(decoration, transform)| crate::game::generate_map::Decoration {
+                kind: decoration.kind,
+                position: (transform.translation.x / TILE_SIZE, transform.translation.y / TILE_SIZE),
+                hits: decoration.hits,
+                respawn_at: decoration.respawn_at,
+            })
+            .collect();
+        let structures = children
+            .into_iter()
+            .flatten()
+            .filter_map(|child| self.structure_query.get(*child).ok())
+            .map(|(structure, transform, health)| crate::game::generate_map::Structure {
+                kind: structure.kind,
+                position: TilePos::from_world(transform.translation.truncate()),
+                health: health.current,
+            })
+            .collect();
+        let items = children
+            .into_iter()
+            .flatten()
+            .filter_map(|child| self.item_query.get(*child).ok())
+            .map(|(world_item, transform)| DroppedItem {
+                item: self.items.get(world_item.stack.item).name.clone(),
+                count: world_item.stack.count,
+                position: (transform.translation.x / TILE_SIZE, transform.translation.y / TILE_SIZE),
+            })
+            .collect();
+        let stations = children
+            .into_iter()
+            .flatten()
+            .filter_map(|child| self.station_query.get(*child).ok())
+            .map(|(station, transform)| PlacedStation {
+                station: self.stations.get(station.kind).name.clone(),
+                position: TilePos::from_world(transform.translation.truncate()),
+            })
+            .collect();
+        let chunk_data = ChunkData {
+            tiles: chunk_tiles.tiles.clone(),
+            decorations,
+            structures,
+            items,
+            stations,
+        };
+
+        self.store.insert(chunk_pos, chunk_data, &map_state.biomes, &map_state.decorations, &map_state.structures);
+    }
+
+    // Сохраняет все загруженные изменённые чанки, не выгружая их
+    fn store_loaded(&mut self, map_state: &MapState) {
+        for (&chunk_pos, &entity) in &map_state.loaded_chunks {
+            self.store_chunk(chunk_pos, entity, map_state);
+        }
+    }
+
+    // Отправляет изменённые регионы на запись в фон и сообщает об ошибках прошлых записей
+    fn flush(&mut self, map_state: &MapState) {
+        for err in self.store.poll_writes() {
+            println!("Ошибка сохранения чанков: {}", err);
+        }
+        self.store.flush(&map_state.biomes, &map_state.decorations, &map_state.structures);
+    }
+
+    // Записывает изменённые регионы и ждёт, пока запись закончится
+    fn flush_now(&mut self, map_state: &MapState) {
+        if let Err(err) = self.store.flush_now(&map_state.biomes, &map_state.decorations, &map_state.structures) {
+            println!("Ошибка сохранения чанков: {}", err);
+        }
+    }
+
+    // Переносит изменённые чанки в каталог слота, чтобы они попали в сохранение
+    pub fn commit(&mut self, map_state: &MapState) -> Result<(), String> {
+        self.store_loaded(map_state);
+        self.store.commit(&map_state.biomes, &map_state.decorations, &map_state.structures)
+    }
+
+    // Дальше чанки читаются и пишутся в другие каталоги. Несохранённые изменения старого мира
+    // выбрасываются, но сначала дописываются фоновые записи, чтобы они не попали в новый work_dir
+    pub fn open(&mut self, dir: PathBuf, work_dir: PathBuf) {
+        if let Err(err) = self.store.wait() {
+            println!("Ошибка сохранения чанков: {}", err);
+        }
+        *self.store = ChunkStore::new(dir, work_dir);
+    }
+}
+
+// Чанк появился в мире. Сущность чанка доступна системам со следующего кадра
+#[derive(Event)]
+pub struct ChunkSpawned(pub ChunkPosition);
+
+// Сущности с этим компонентом рисуются тем выше, чем ниже они на экране
+#[derive(Component)]
+pub struct YSort;
+
+// Сколько чанков может генерироваться в фоне одновременно
+const MAX_PENDING_CHUNKS: usize = 8;
+// Сколько готовых чанков спавнится за кадр, чтобы не было рывков
+const CHUNK_SPAWN_BUDGET: usize = 2;
+// Как часто выгруженные чанки пишутся на диск, в секундах
+const CHUNK_WRITE_INTERVAL: f32 = 2.0;
+// Чанк в радиусе симуляции: сущности на нём продолжают обновляться, даже если его не видно
+#[derive(Component)]
+pub struct SimulatedChunk;
+
+// Радиусы в чанках, считаются от игрока до центра чанка. Задаются в assets/map_settings.ron
+#[derive(Resource, Deserialize)]
+pub struct MapSettings {
+    // Чанки в этом радиусе видны
+    pub render_distance: f32,
+    // Чанки в этом радиусе загружены и симулируются
+    pub simulation_distance: f32,
+    // Загруженные чанки выгружаются только за этим радиусом,
+    // чтобы на границе чанков ничего не мигало
+    pub unload_distance: f32,
+}
+
+impl Default for MapSettings {
+    fn default() -> Self {
+        Self {
+            render_distance: 2.5,
+            simulation_distance: 3.0,
+            unload_distance: 4.5,
+        }
+    }
+}
+
+impl MapSettings {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let settings: Self = load_ron(path)?;
+        // Иначе чанк на границе загрузки выгружался бы и загружался снова при каждом шаге
+        if settings.unload_distance <= settings.load_distance() + 1.0 {
+            return Err(format!(
+                "{}: радиус выгрузки {} должен быть больше радиуса загрузки {} хотя бы на чанк",
+                path.display(),
+                settings.unload_distance,
+                settings.load_distance()
+            ));
+        }
+        Ok(settings)
+    }
+
+    fn load_distance(&self) -> f32 {
+        self.render_distance.max(self.simulation_distance)
+    }
+}
+
+// Слой над землёй, вокруг которого раскладываются отсортированные спрайты
+const YSORT_Z: f32 = 10.0;
+const YSORT_SCALE: f32 = 0.001;
+
+fn load_world_data() -> Result<(BiomeRegistry, DecorationRegistry, StructureRegistry), String> {
+    let decorations = DecorationRegistry::load(&assets_dir().join("decorations"))?;
+    let biomes = BiomeRegistry::load(&assets_dir().join("biomes"), &decorations)?;
+    let structures = StructureRegistry::load(&assets_dir().join("structures"))?;
+    Ok((biomes, decorations, structures))
+}
+
+pub fn setup_map(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let (biomes, decorations, structures) = load_world_data()
+        .unwrap_or_else(|err| panic!("Не удалось загрузить данные мира: {}", err));
 
-pub fn setup_map(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
+    let settings = MapSettings::load(&assets_dir().join("map_settings.ron")).unwrap_or_else(|err| {
+        println!("Настройки карты не загружены, используются стандартные: {}", err);
+        MapSettings::default()
+    });
+    commands.insert_resource(settings);
+    commands.insert_resource(TileAtlas::load(&asset_server, &biomes));
+    // Сид и хранилище чанков заменяются при загрузке слота сохранения
     commands.insert_resource(MapState {
         loaded_chunks: HashMap::new(),
-        seed: rand::random(),
+        pending_chunks: HashMap::new(),
+        ready_chunks: HashMap::new(),
+        generator: Arc::new(TerrainGenerator::new(rand::random(), TerrainSettings::default())),
+        biomes: Arc::new(biomes),
+        decorations,
+        structures,
+        write_timer: Timer::from_seconds(CHUNK_WRITE_INTERVAL, TimerMode::Repeating),
+    });
+    commands.insert_resource(WorldDataWatcher {
+        biomes: DirWatcher::new(assets_dir().join("biomes")),
+        decorations: DirWatcher::new(assets_dir().join("decorations")),
+        structures: DirWatcher::new(assets_dir().join("structures")),
     });
 }
 
-pub fn update_map(
+// Горячая перезагрузка биомов, декораций и построек: при изменении файлов все чанки генерируются заново
+pub fn reload_world_data(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    mut watcher: ResMut<WorldDataWatcher>,
+    mut map_state: ResMut<MapState>,
+    mut atlas: ResMut<TileAtlas>,
+    mut persistence: ChunkPersistence,
+) {
+    // Опрашиваем все каталоги, чтобы не копить изменения в остальных
+    let biomes_changed = watcher.biomes.poll();
+    let decorations_changed = watcher.decorations.poll();
+    let structures_changed = watcher.structures.poll();
+    if !biomes_changed && !decorations_changed && !structures_changed {
+        return;
+    }
+
+    match load_world_data() {
+        Ok((biomes, decorations, structures)) => {
+            // Изменённые чанки сохраняются со старыми реестрами, а при чтении
+            // индексы биомов, декораций и построек переводятся в новые по именам
+            let loaded: Vec<_> = map_state.loaded_chunks.drain().collect();
+            for (chunk_pos, entity) in loaded {
+                persistence.store_chunk(chunk_pos, entity, &map_state);
+                commands.entity(entity).despawn_recursive();
+            }
+            persistence.flush_now(&map_state);
+            persistence.store.clear_cache();
+
+            // Биомы могли сослаться на новые текстуры, атлас собирается заново
+            *atlas = TileAtlas::load(&asset_server, &biomes);
+            map_state.biomes = Arc::new(biomes);
+            map_state.decorations = decorations;
+            map_state.structures = structures;
+            // Фоновые задачи работают со старыми данными, их результат не нужен
+            map_state.pending_chunks.clear();
+            map_state.ready_chunks.clear();
+            println!("Данные мира перезагружены");
+        }
+        Err(err) => println!("Ошибка загрузки данных мира: {}", err),
+    }
+}
+
+// Чем ниже спрайт относительно камеры, тем ближе он к зрителю
+pub fn y_sort(
+    camera_query: Query<&Transform, (With<Camera>, Without<YSort>)>,
+    mut query: Query<&mut Transform, With<YSort>>,
+) {
+    let camera_y = camera_query.get_single().map(|transform| transform.translation.y).unwrap_or(0.0);
+    for mut transform in query.iter_mut() {
+        transform.translation.z = YSORT_Z - (transform.translation.y - camera_y) * YSORT_SCALE;
+    }
+}
+
+// Расстояние от точки мира до центра чанка, в чанках
+fn chunk_distance(chunk_pos: ChunkPosition, point: Vec2) -> f32 {
+    chunk_pos.center().distance(point) / CHUNK_WORLD_SIZE
+}
+
+pub fn update_map(
+    mut commands: Commands,
+    settings: Res<MapSettings>,
     player_query: Query<&Transform, With<crate::game::player::Player>>,
     mut map_state: ResMut<MapState>,
+    mut persistence: ChunkPersistence,
 ) {
+    for err in persistence.store.poll_reads() {
+        println!("Не удалось прочитать регион: {}", err);
+    }
+
     if let Ok(player_transform) = player_query.get_single() {
-        let player_chunk = ChunkPosition(
-            (player_transform.translation.x / (CHUNK_SIZE as f32 * 32.0)).floor() as i32,
-            (player_transform.translation.y / (CHUNK_SIZE as f32 * 32.0)).floor() as i32,
-        );
+        let center = player_transform.translation.truncate();
+        let load_distance = settings.load_distance();
+        let unload_distance = settings.unload_distance;
 
-        // Определяем какие чанки должны быть загружены
+        // Определяем какие чанки должны быть загружены: все, чей центр попадает в круг
         let mut chunks_to_load = Vec::new();
-        for y in -RENDER_DISTANCE..=RENDER_DISTANCE {
-            for x in -RENDER_DISTANCE..=RENDER_DISTANCE {
-                let chunk_pos = ChunkPosition(
-                    player_chunk.0 + x,
-                    player_chunk.1 + y,
-                );
-                chunks_to_load.push(chunk_pos);
+        let reach = load_distance.ceil() as i32;
+        let player_chunk = ChunkPosition::from_world(center);
+        for y in player_chunk.1 - reach..=player_chunk.1 + reach {
+            for x in player_chunk.0 - reach..=player_chunk.0 + reach {
+                let chunk_pos = ChunkPosition(x, y);
+                if chunk_distance(chunk_pos, center) <= load_distance {
+                    chunks_to_load.push(chunk_pos);
+                }
             }
         }
 
-        // Удаляем дальние чанки
+        // Удаляем чанки за радиусом выгрузки
         let chunks_to_remove: Vec<_> = map_state.loaded_chunks
             .keys()
-            .filter(|pos| !chunks_to_load.contains(pos))
+            .filter(|pos| chunk_distance(**pos, center) > unload_distance)
             .copied()
             .collect();
 
+        // На диск выгруженные чанки попадают позже, в write_chunks
         for pos in chunks_to_remove {
             if let Some(entity) = map_state.loaded_chunks.remove(&pos) {
+                persistence.store_chunk(pos, entity, &map_state);
                 commands.entity(entity).despawn_recursive();
             }
         }
 
-        // Загружаем новые чанки
-        for chunk_pos in chunks_to_load {
-            if !map_state.loaded_chunks.contains_key(&chunk_pos) {
-                let chunk_entity = spawn_chunk(&mut commands, &asset_server, chunk_pos, map_state.seed);
-                map_state.loaded_chunks.insert(chunk_pos, chunk_entity);
-                println!("Загружен чанк: {:?}", chunk_pos); // Отладочный вывод
+        // Ушедшие из зоны задачи отменяются вместе с удалением Task
+        map_state.pending_chunks.retain(|pos, _| chunk_distance(*pos, center) <= unload_distance);
+        map_state.ready_chunks.retain(|pos, _| chunk_distance(*pos, center) <= unload_distance);
+
+        // Регионы, до которых игрок уже далеко, уходят из памяти
+        let needed = map_state.loaded_chunks.keys().chain(&chunks_to_load).copied();
+        persistence.store.evict(needed);
+
+        // Запускаем генерацию недостающих чанков, ближайшие первыми
+        let mut missing: Vec<_> = chunks_to_load
+            .into_iter()
+            .filter(|pos| {
+                !map_state.loaded_chunks.contains_key(pos)
+                    && !map_state.pending_chunks.contains_key(pos)
+                    && !map_state.ready_chunks.contains_key(pos)
+            })
+            .collect();
+        missing.sort_by(|a, b| chunk_distance(*a, center).total_cmp(&chunk_distance(*b, center)));
+
+        let mut free_slots = MAX_PENDING_CHUNKS.saturating_sub(map_state.pending_chunks.len());
+        let pool = AsyncComputeTaskPool::get();
+        for chunk_pos in missing {
+            // Изменённые чанки читаются из хранилища вместо генерации и спавнятся в общей очереди
+            match persistence.store.lookup(chunk_pos, &map_state.biomes, &map_state.decorations, &map_state.structures) {
+                StoredChunk::Found(chunk_data) => {
+                    map_state.ready_chunks.insert(chunk_pos, chunk_data);
+                    continue;
+                }
+                // Пока регион читается, чанк ждёт, иначе он сгенерировался бы без изменений
+                StoredChunk::Loading => continue,
+                StoredChunk::Missing => {}
             }
+            if free_slots == 0 {
+                continue;
+            }
+            free_slots -= 1;
+
+            let generator = map_state.generator.clone();
+            let biomes = map_state.biomes.clone();
+            let task = pool.spawn(async move { generate_chunk(&generator, &biomes, chunk_pos) });
+            map_state.pending_chunks.insert(chunk_pos, task);
+        }
+    }
+}
+
+// Выгруженные чанки пишутся на диск пачками и в фоне, чтобы запись регионов не тормозила кадр
+pub fn write_chunks(
+    time: Res<Time>,
+    mut map_state: ResMut<MapState>,
+    mut persistence: ChunkPersistence,
+) {
+    if map_state.write_timer.tick(time.delta()).just_finished() {
+        persistence.flush(&map_state);
+    }
+}
+
+// Прячет чанки за радиусом отрисовки и отмечает чанки в радиусе симуляции
+pub fn update_chunk_activity(
+    mut commands: Commands,
+    settings: Res<MapSettings>,
+    map_state: Res<MapState>,
+    player_query: Query<&Transform, With<crate::game::player::Player>>,
+    mut chunk_query: Query<(&mut Visibility, Has<SimulatedChunk>)>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let center = player_transform.translation.truncate();
+
+    for (&chunk_pos, &entity) in &map_state.loaded_chunks {
+        let Ok((mut visibility, simulated)) = chunk_query.get_mut(entity) else {
+            continue;
+        };
+        let distance = chunk_distance(chunk_pos, center);
+
+        let wanted = if distance <= settings.render_distance { Visibility::Inherited } else { Visibility::Hidden };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+
+        let should_simulate = distance <= settings.simulation_distance;
+        if should_simulate && !simulated {
+            commands.entity(entity).insert(SimulatedChunk);
+        } else if !should_simulate && simulated {
+            commands.entity(entity).remove::<SimulatedChunk>();
+        }
+    }
+}
+
+// Забирает готовые чанки из фоновых задач и спавнит их в пределах бюджета кадра
+pub fn spawn_generated_chunks(
+    mut commands: Commands,
+    contents: ChunkContents,
+    atlas: Res<TileAtlas>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    player_query: Query<&Transform, With<crate::game::player::Player>>,
+    mut map_state: ResMut<MapState>,
+    mut spawned_events: EventWriter<ChunkSpawned>,
+) {
+    let finished: Vec<_> = map_state
+        .pending_chunks
+        .iter_mut()
+        .filter_map(|(pos, task)| future::block_on(future::poll_once(task)).map(|data| (*pos, data)))
+        .collect();
+    for (chunk_pos, chunk_data) in finished {
+        map_state.pending_chunks.remove(&chunk_pos);
+        map_state.ready_chunks.insert(chunk_pos, chunk_data);
+    }
+
+    // Пока атлас не собран, готовые чанки просто ждут
+    let Some(material) = atlas.material() else {
+        return;
+    };
+
+    let center = player_query
+        .get_single()
+        .map(|transform| transform.translation.truncate())
+        .unwrap_or_default();
+    let mut ready: Vec<_> = map_state.ready_chunks.keys().copied().collect();
+    ready.sort_by(|a, b| chunk_distance(*a, center).total_cmp(&chunk_distance(*b, center)));
+
+    for chunk_pos in ready.into_iter().take(CHUNK_SPAWN_BUDGET) {
+        if let Some(chunk_data) = map_state.ready_chunks.remove(&chunk_pos) {
+            let mesh = meshes.add(empty_chunk_mesh());
+            let chunk_entity = spawn_chunk(&mut commands, &contents, chunk_data, mesh, material.clone(), &map_state);
+            map_state.loaded_chunks.insert(chunk_pos, chunk_entity);
+            spawned_events.send(ChunkSpawned(chunk_pos));
+            println!("Загружен чанк: {:?}", chunk_pos); // Отладочный вывод
         }
     }
 }
 
 fn spawn_chunk(
     commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
-    chunk_pos: ChunkPosition,
-    seed: u64,
+    contents: &ChunkContents,
+    chunk_data: ChunkData,
+    mesh: Handle<Mesh>,
+    material: Handle<ColorMaterial>,
+    map_state: &MapState,
 ) -> Entity {
-    let chunk = commands.spawn(SpatialBundle::default()).id();
-    let tiles = generate_chunk(chunk_pos, seed);
-
-    for tile in tiles {
-        let (texture_path, rotation) = match tile.tile_type {
-            TileType::Grass { rotation } => {
-                match tile.biome {
-                    BiomeType::Summer => (format!("summer/grass_{}.png", rand::random::<u8>() % 3), rotation),
-                    BiomeType::Winter => (format!("winter/grass_{}.png", rand::random::<u8>() % 3), rotation),
-                }
-            },
-            TileType::Water => {
-                match tile.biome {
-                    BiomeType::Summer => ("summer/water.png".to_string(), 0.0),
-                    BiomeType::Winter => ("winter/water.png".to_string(), 0.0),
-                }
-            },
-            TileType::Dirt => {
-                match tile.biome {
-                    BiomeType::Summer => ("summer/dirt.png".to_string(), 0.0),
-                    BiomeType::Winter => ("winter/dirt.png".to_string(), 0.0),
-                }
-            },
-            TileType::Road => ("common/road.png".to_string(), 0.0),
-            TileType::BiomeBorder { from, to } => {
-                (format!("borders/{}_to_{}.png", 
-                    if from == BiomeType::Summer { "summer" } else { "winter" },
-                    if to == BiomeType::Summer { "summer" } else { "winter" }
-                ), 0.0)
-            },
-        };
+    let asset_server = &contents.asset_server;
+    // Все тайлы чанка рисуются одним мешем, он заполняется в update_chunk_meshes
+    let chunk = commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: mesh.into(),
+            material,
+            ..Default::default()
+        },
+        ChunkTiles { tiles: chunk_data.tiles },
+    )).id();
 
-        commands.spawn(SpriteBundle {
-            transform: Transform {
-                translation: Vec3::new(
-                    tile.position.0 as f32 * 32.0,
-                    tile.position.1 as f32 * 32.0,
-                    0.0,
+    for decoration in chunk_data.decorations {
+        let definition = map_state.decorations.get(decoration.kind);
+        let mut entity = commands.spawn((
+            SpriteBundle {
+                transform: Transform::from_xyz(
+                    decoration.position.0 * TILE_SIZE,
+                    decoration.position.1 * TILE_SIZE,
+                    YSORT_Z,
                 ),
-                rotation: Quat::from_rotation_z(rotation.to_radians()),
-                scale: Vec3::new(32.0, 32.0, 1.0),
-            },
-            sprite: Sprite {
-                custom_size: Some(Vec2::new(1.0, 1.0)),
+                sprite: Sprite {
+                    custom_size: Some(Vec2::splat(TILE_SIZE)),
+                    ..Default::default()
+                },
+                texture: asset_server.load(definition.texture.as_str()),
                 ..Default::default()
             },
-            texture: asset_server.load(&texture_path),
-            ..Default::default()
-        }).set_parent(chunk);
+            Decoration {
+                kind: decoration.kind,
+                hits: decoration.hits,
+                respawn_at: decoration.respawn_at,
+            },
+            YSort,
+        ));
+        entity.set_parent(chunk);
+
+        // Добытая декорация ждёт восстановления невидимой и не мешает ходить
+        if decoration.respawn_at.is_some() {
+            entity.insert((Visibility::Hidden, Depleted));
+        } else if let Some(radius) = definition.collision_radius {
+            entity.insert(Collider { radius: radius * TILE_SIZE });
+        }
+    }
+
+    for structure in chunk_data.structures {
+        let entity = spawn_structure(
+            commands,
+            asset_server,
+            &map_state.structures,
+            structure.kind,
+            structure.position,
+            structure.health,
+        );
+        commands.entity(entity).set_parent(chunk);
+    }
+
+    for item in chunk_data.items {
+        let Some(kind) = contents.items.find(&item.item) else {
+            println!("Неизвестный предмет на земле: {}", item.item);
+            continue;
+        };
+        let stack = ItemStack { item: kind, count: item.count };
+        let position = Vec2::new(item.position.0, item.position.1) * TILE_SIZE;
+        let entity = spawn_world_item(commands, asset_server, &contents.items, stack, position, IMMEDIATE_PICKUP);
+        commands.entity(entity).set_parent(chunk);
+    }
+
+    for station in chunk_data.stations {
+        let Some(kind) = contents.stations.find(&station.station) else {
+            println!("Неизвестная станция в чанке: {}", station.station);
+            continue;
+        };
+        let entity = spawn_station(commands, asset_server, &contents.stations, kind, station.position.to_world());
+        commands.entity(entity).set_parent(chunk);
     }
 
     chunk
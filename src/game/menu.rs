@@ -1,9 +1,16 @@
 use bevy::prelude::*;
 use bevy::app::AppExit;
+use crate::game::health::{Dead, RespawnPlayer};
+use crate::game::player::Player;
+use crate::game::save::{LoadGame, SaveGame, SaveSlots};
 
-#[derive(Debug, Resource)]
+#[derive(Debug, Default, Resource)]
 pub struct GameState {
     pub paused: bool,
+    // Курсор над интерфейсом, клики не должны доходить до мира
+    pub pointer_over_ui: bool,
+    // Включён режим строительства: кнопки мыши ставят и разбирают постройки
+    pub building: bool,
 }
 
 #[derive(Component)]
@@ -13,16 +20,16 @@ pub struct PauseMenu;
 pub struct PauseOverlay;
 
 #[derive(Component)]
-enum MenuButton {
+pub struct DeathScreen;
+
+#[derive(Component)]
+pub enum MenuButton {
     Resume,
+    Save,
+    Load(String),
     Settings,
     Exit,
-}
-
-impl Default for GameState {
-    fn default() -> Self {
-        Self { paused: false }
-    }
+    Respawn,
 }
 
 pub fn setup_menu(mut commands: Commands) {
@@ -41,6 +48,7 @@ pub fn pause_input(
 pub fn pause_menu(
     mut commands: Commands,
     game_state: Res<GameState>,
+    slots: Res<SaveSlots>,
     menu_query: Query<Entity, Or<(With<PauseMenu>, With<PauseOverlay>)>>,
 ) {
     // Удаляем старое меню если оно есть
@@ -96,12 +104,67 @@ pub fn pause_menu(
                 ));
 
                 spawn_button(parent, "Продолжить", MenuButton::Resume);
+                spawn_button(parent, "Сохранить", MenuButton::Save);
+                for slot in slots.available.iter().filter(|slot| **slot != slots.current) {
+                    spawn_button(parent, &format!("Загрузить: {}", slot), MenuButton::Load(slot.clone()));
+                }
                 spawn_button(parent, "Настройки", MenuButton::Settings);
                 spawn_button(parent, "Выйти", MenuButton::Exit);
             });
     }
 }
 
+// Экран смерти висит, пока игрок мёртв
+pub fn death_screen(
+    mut commands: Commands,
+    player_query: Query<(), (With<Player>, With<Dead>)>,
+    screen_query: Query<Entity, With<DeathScreen>>,
+) {
+    let dead = !player_query.is_empty();
+    let shown = !screen_query.is_empty();
+
+    if !dead {
+        for entity in screen_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    if shown {
+        return;
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(20.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.3, 0.0, 0.0, 0.7).into(),
+                ..default()
+            },
+            DeathScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Вы погибли",
+                TextStyle {
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+
+            spawn_button(parent, "Возродиться", MenuButton::Respawn);
+        });
+}
+
 fn spawn_button(parent: &mut ChildBuilder, text: &str, button_type: MenuButton) {
     parent
         .spawn((
@@ -136,6 +199,9 @@ pub fn handle_buttons(
         (Changed<Interaction>, With<Button>),
     >,
     mut game_state: ResMut<GameState>,
+    mut save_events: EventWriter<SaveGame>,
+    mut load_events: EventWriter<LoadGame>,
+    mut respawn_events: EventWriter<RespawnPlayer>,
     mut exit: EventWriter<AppExit>,
 ) {
     for (interaction, button_type, mut color) in &mut interaction_query {
@@ -145,13 +211,25 @@ pub fn handle_buttons(
                     MenuButton::Resume => {
                         game_state.paused = false;
                     }
+                    MenuButton::Save => {
+                        save_events.send(SaveGame);
+                    }
+                    MenuButton::Load(slot) => {
+                        load_events.send(LoadGame { slot: slot.clone() });
+                        game_state.paused = false;
+                    }
                     MenuButton::Settings => {
                         // TODO: Добавить открытие настроек
                         println!("Открываем настройки");
                     }
                     MenuButton::Exit => {
+                        // save_game идёт после handle_buttons, так что сохранение успеет до выхода
+                        save_events.send(SaveGame);
                         exit.send(AppExit);
                     }
+                    MenuButton::Respawn => {
+                        respawn_events.send(RespawnPlayer);
+                    }
                 }
                 *color = Color::rgb(0.5, 0.5, 0.5).into();
             }
@@ -1,5 +1,32 @@
 pub mod player; 
+pub mod bullet;
+pub mod weapon;
+pub mod health;
+pub mod survival;
+pub mod item;
+pub mod inventory;
+pub mod inventory_ui;
+pub mod station;
+pub mod crafting;
+pub mod clock;
+pub mod harvest;
+pub mod structure;
+pub mod building;
+pub mod enemy;
+pub mod spawning;
 pub mod map;    
 pub mod generate_map;
+pub mod world;
+pub mod biome;
+pub mod decoration;
+pub mod data;
+pub mod terrain;
+pub mod roads;
+pub mod rivers;
+pub mod tilemap;
+pub mod tile_query;
+pub mod collision;
+pub mod chunk_store;
+pub mod save;
 pub mod debug;
 pub mod menu;
\ No newline at end of file
@@ -1,31 +1,68 @@
 use bevy::prelude::*;
+use crate::game::collision::{move_and_slide, Collider};
+use crate::game::crafting::Crafter;
+use crate::game::harvest::Harvester;
+use crate::game::health::{Dead, Health, Invulnerability};
+use crate::game::inventory::{Inventory, INVENTORY_SLOTS, MAX_CARRY_WEIGHT};
+use crate::game::map::YSort;
+use crate::game::survival::Survival;
+use crate::game::tile_query::TileQuery;
+use crate::game::weapon::Weapon;
+use crate::game::world::TILE_SIZE;
 
 #[derive(Component)]
 pub struct Player;
 
+const PLAYER_SPEED: f32 = 200.0;
+// Радиус столкновений игрока, меньше половины тайла, чтобы проходить между препятствиями
+const PLAYER_RADIUS: f32 = 10.0;
+const PLAYER_HEALTH: f32 = 100.0;
+// Неуязвимость после полученного удара, в секундах
+const PLAYER_INVULNERABILITY: f32 = 1.0;
+
 pub fn spawn_player(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.spawn((
         SpriteBundle {
             transform: Transform::from_xyz(0.0, 0.0, 2.0), 
             texture: asset_server.load("player/player.png"),
             sprite: Sprite {
-                custom_size: Some(Vec2::new(32.0, 32.0)),
+                custom_size: Some(Vec2::splat(TILE_SIZE)),
                 ..Default::default()
             },
             ..Default::default()
         },
         Player,
+        Health::new(PLAYER_HEALTH),
+        Invulnerability::new(PLAYER_INVULNERABILITY),
+        Survival::default(),
+        Inventory::new(INVENTORY_SLOTS, MAX_CARRY_WEIGHT),
+        Crafter::default(),
+        Harvester::default(),
+        Collider { radius: PLAYER_RADIUS },
+        Weapon::default(),
+        YSort,
     ));
 }
 
 pub fn player_movement(
     time: Res<Time>,
     keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<&mut Transform, With<Player>>,
+    tiles: TileQuery,
+    mut query: Query<&mut Transform, (With<Player>, Without<Dead>)>,
+    collider_query: Query<(&GlobalTransform, &Collider), Without<Player>>,
 ) {
     if let Ok(mut transform) = query.get_single_mut() {
-        let mut direction = Vec3::ZERO;
-        let speed = 200.0; // Скорость движения
+        let mut direction = Vec2::ZERO;
+        let position = transform.translation.truncate();
+
+        // Скорость зависит от тайла под ногами и от биома (например, снег)
+        let speed = tiles
+            .tile_at_world(position)
+            .map(|tile| {
+                let biome = tiles.map_state().biomes().get(tile.biome);
+                PLAYER_SPEED * tile.tile_type.speed_multiplier() * biome.movement_speed
+            })
+            .unwrap_or(PLAYER_SPEED);
 
         if keyboard_input.pressed(KeyCode::W) {
             direction.y += 1.0;
@@ -40,9 +77,14 @@ pub fn player_movement(
             direction.x += 1.0;
         }
 
-        if direction != Vec3::ZERO {
-            direction = direction.normalize();
-            transform.translation += direction * speed * time.delta_seconds();
+        if direction != Vec2::ZERO {
+            let delta = direction.normalize() * speed * time.delta_seconds();
+            let colliders = collider_query
+                .iter()
+                .map(|(transform, collider)| (transform.translation().truncate(), collider));
+            let moved = move_and_slide(&tiles, colliders, position, delta, PLAYER_RADIUS);
+            transform.translation.x = moved.x;
+            transform.translation.y = moved.y;
         }
     }
 }
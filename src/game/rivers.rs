@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use crate::game::generate_map::grid_hash;
+use crate::game::terrain::{GenerationCache, TerrainGenerator};
+
+// В каждом регионе может начинаться не больше одной реки
+const RIVER_REGION_SIZE: i32 = 128;
+const RIVER_SALT: u64 = 200;
+// Процент регионов, в которых есть исток
+const RIVER_CHANCE: u64 = 60;
+// Реки начинаются только на возвышенностях
+const SOURCE_MIN_ELEVATION: f32 = 0.55;
+const MAX_RIVER_LENGTH: i32 = 192;
+// После стольких тайлов река становится шире
+const WIDEN_AFTER: usize = 64;
+
+const STEPS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+// Проложенные реки по региону истока. Каждый чанк проверяет истоки из пары десятков
+// регионов вокруг, и без кеша одна и та же река трассировалась бы для каждого чанка заново
+pub type RiverCache = GenerationCache<(i32, i32), Arc<Vec<(i32, i32)>>>;
+
+fn river_source(generator: &TerrainGenerator, region: (i32, i32)) -> Option<(i32, i32)> {
+    let hash = grid_hash(generator.seed(), region, RIVER_SALT);
+    if hash % 100 >= RIVER_CHANCE {
+        return None;
+    }
+
+    let size = RIVER_REGION_SIZE as u64;
+    let source = (
+        region.0 * RIVER_REGION_SIZE + ((hash >> 8) % size) as i32,
+        region.1 * RIVER_REGION_SIZE + ((hash >> 32) % size) as i32,
+    );
+
+    if generator.elevation_at(source.0, source.1) < SOURCE_MIN_ELEVATION {
+        return None;
+    }
+    Some(source)
+}
+
+// Река всегда течёт в самого низкого ещё не пройденного соседа, пока не дойдёт
+// до озера, не кончится длина или не упрётся в низину, из которой некуда стекать
+fn trace_river(generator: &TerrainGenerator, source: (i32, i32)) -> Vec<(i32, i32)> {
+    let mut path = vec![source];
+    let mut visited = HashSet::from([source]);
+    let mut current = source;
+    let mut elevation = generator.elevation_at(source.0, source.1);
+
+    while path.len() < MAX_RIVER_LENGTH as usize && !generator.is_water_at(current.0, current.1) {
+        let next = STEPS
+            .iter()
+            .map(|&(dx, dy)| (current.0 + dx, current.1 + dy))
+            .filter(|tile| !visited.contains(tile))
+            .map(|tile| (tile, generator.elevation_at(tile.0, tile.1)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        let Some((tile, next_elevation)) = next.filter(|&(_, next_elevation)| next_elevation <= elevation) else {
+            break;
+        };
+        visited.insert(tile);
+        path.push(tile);
+        current = tile;
+        elevation = next_elevation;
+    }
+
+    path
+}
+
+// Тайлы рек внутри прямоугольника min..=max, включая реки из соседних регионов
+pub fn river_tiles(generator: &TerrainGenerator, min: (i32, i32), max: (i32, i32)) -> HashSet<(i32, i32)> {
+    let region_min = (
+        (min.0 - MAX_RIVER_LENGTH).div_euclid(RIVER_REGION_SIZE),
+        (min.1 - MAX_RIVER_LENGTH).div_euclid(RIVER_REGION_SIZE),
+    );
+    let region_max = (
+        (max.0 + MAX_RIVER_LENGTH).div_euclid(RIVER_REGION_SIZE),
+        (max.1 + MAX_RIVER_LENGTH).div_euclid(RIVER_REGION_SIZE),
+    );
+    let inside = |(x, y): (i32, i32)| x >= min.0 && x <= max.0 && y >= min.1 && y <= max.1;
+
+    let mut tiles = HashSet::new();
+    for region_y in region_min.1..=region_max.1 {
+        for region_x in region_min.0..=region_max.0 {
+            let Some(source) = river_source(generator, (region_x, region_y)) else {
+                continue;
+            };
+
+            // Река короче MAX_RIVER_LENGTH шагов, дальше она не дотянется
+            let distance = (min.0 - source.0).max(source.0 - max.0).max(0)
+                + (min.1 - source.1).max(source.1 - max.1).max(0);
+            if distance > MAX_RIVER_LENGTH {
+                continue;
+            }
+
+            let river = generator
+                .river_cache()
+                .get_or_insert_with((region_x, region_y), || Arc::new(trace_river(generator, source)));
+            for (index, &(x, y)) in river.iter().enumerate() {
+                if inside((x, y)) {
+                    tiles.insert((x, y));
+                }
+                if index >= WIDEN_AFTER {
+                    for tile in [(x + 1, y), (x, y + 1)] {
+                        if inside(tile) {
+                            tiles.insert(tile);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::terrain::TerrainSettings;
+
+    const SEED: u64 = 12345;
+    // Квадрат регионов рек вокруг начала координат
+    const AREA: i32 = 6;
+
+    fn rivers(generator: &TerrainGenerator) -> Vec<((i32, i32), Vec<(i32, i32)>)> {
+        let mut rivers = Vec::new();
+        for region_y in -AREA / 2..AREA / 2 {
+            for region_x in -AREA / 2..AREA / 2 {
+                if let Some(source) = river_source(generator, (region_x, region_y)) {
+                    rivers.push((source, trace_river(generator, source)));
+                }
+            }
+        }
+        assert!(!rivers.is_empty(), "в проверяемых регионах нет ни одной реки");
+        rivers
+    }
+
+    #[test]
+    fn river_never_flows_uphill() {
+        let generator = TerrainGenerator::new(SEED, TerrainSettings::default());
+        for (_, river) in rivers(&generator) {
+            for pair in river.windows(2) {
+                let from = generator.elevation_at(pair[0].0, pair[0].1);
+                let to = generator.elevation_at(pair[1].0, pair[1].1);
+                assert!(to <= from, "река поднимается с {:?} на {:?}", pair[0], pair[1]);
+            }
+        }
+    }
+
+    // river_tiles полагается на то, что река не уходит от истока дальше MAX_RIVER_LENGTH
+    #[test]
+    fn river_stays_bounded() {
+        let generator = TerrainGenerator::new(SEED, TerrainSettings::default());
+        for (source, river) in rivers(&generator) {
+            assert_eq!(river[0], source);
+            assert!(river.len() <= MAX_RIVER_LENGTH as usize, "река длиннее {} тайлов", MAX_RIVER_LENGTH);
+            for pair in river.windows(2) {
+                let step = (pair[1].0 - pair[0].0).abs() + (pair[1].1 - pair[0].1).abs();
+                assert_eq!(step, 1, "река прерывается между {:?} и {:?}", pair[0], pair[1]);
+            }
+            for &(x, y) in &river {
+                assert!((x - source.0).abs() + (y - source.1).abs() <= MAX_RIVER_LENGTH);
+            }
+        }
+    }
+
+    // Соседние чанки видят одну и ту же реку, так что на их общем крае она не рвётся
+    #[test]
+    fn adjacent_windows_agree_on_shared_edge() {
+        let generator = TerrainGenerator::new(SEED, TerrainSettings::default());
+        let (_, river) = rivers(&generator).into_iter().max_by_key(|(_, river)| river.len()).unwrap();
+        // Край проходит через середину реки
+        let (x, y) = river[river.len() / 2];
+        let size = 16;
+
+        let left = river_tiles(&generator, (x - size, y - size), (x - 1, y + size));
+        let right = river_tiles(&generator, (x, y - size), (x + size - 1, y + size));
+        let both = river_tiles(&generator, (x - size, y - size), (x + size - 1, y + size));
+
+        assert!(right.contains(&(x, y)));
+        let mut joined = left.clone();
+        joined.extend(&right);
+        assert_eq!(joined, both);
+        for edge_y in y - size..=y + size {
+            assert_eq!(
+                left.contains(&(x - 1, edge_y)),
+                both.contains(&(x - 1, edge_y)),
+                "левое окно расходится с общим на ({}, {})",
+                x - 1,
+                edge_y
+            );
+            assert_eq!(
+                right.contains(&(x, edge_y)),
+                both.contains(&(x, edge_y)),
+                "правое окно расходится с общим на ({}, {})",
+                x,
+                edge_y
+            );
+        }
+    }
+}
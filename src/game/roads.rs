@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::game::generate_map::grid_hash;
+use crate::game::terrain::{GenerationCache, TerrainGenerator};
+use crate::game::world::{ChunkPosition, CHUNK_SIZE};
+
+// Мир разбит на регионы, в каждом регионе не больше одного узла дорог (поселения)
+const REGION_SIZE: i32 = 96;
+// Отступ узла от края региона, чтобы дороги не слипались на границах
+const NODE_MARGIN: i32 = 12;
+// Сколько раз пробуем найти для узла сухое место
+const NODE_ATTEMPTS: u64 = 8;
+// Шаг перебора места поворота дороги
+const ROUTE_STEP: i32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoadPiece {
+    // Прямой участок север-юг, им же рисуются тупики
+    Straight,
+    // Поворот север-восток
+    Corner,
+    // Развилка север-восток-запад
+    Junction,
+    Crossing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoadTile {
+    pub piece: RoadPiece,
+    pub rotation: f32,
+    pub variant: u8,
+}
+
+// Биты направлений идут против часовой стрелки,
+// поэтому поворот на 90 градусов — это циклический сдвиг влево
+const EAST: u8 = 1;
+const NORTH: u8 = 2;
+const WEST: u8 = 4;
+const SOUTH: u8 = 8;
+
+const PIECES: [(RoadPiece, u8); 4] = [
+    (RoadPiece::Straight, NORTH | SOUTH),
+    (RoadPiece::Corner, NORTH | EAST),
+    (RoadPiece::Junction, NORTH | EAST | WEST),
+    (RoadPiece::Crossing, NORTH | EAST | WEST | SOUTH),
+];
+
+struct RoadEdge {
+    // Ломаная из прямых отрезков
+    points: Vec<(i32, i32)>,
+    variant: u8,
+}
+
+// Проложенные пути между узлами соседних регионов. Один путь видят десятки чанков,
+// а прокладка перебирает ломаные и сэмплирует шум вдоль каждой, поэтому считаем её один раз
+pub type RoadCache = GenerationCache<((i32, i32), (i32, i32)), Vec<(i32, i32)>>;
+
+// Узел региона ставится в случайное, но воспроизводимое сухое место
+fn road_node(generator: &TerrainGenerator, region: (i32, i32)) -> Option<(i32, i32)> {
+    let span = (REGION_SIZE - NODE_MARGIN * 2) as u64;
+
+    (0..NODE_ATTEMPTS)
+        .map(|attempt| {
+            let hash = grid_hash(generator.seed(), region, attempt);
+            (
+                region.0 * REGION_SIZE + NODE_MARGIN + (hash % span) as i32,
+                region.1 * REGION_SIZE + NODE_MARGIN + ((hash >> 32) % span) as i32,
+            )
+        })
+        .find(|&(x, y)| !generator.is_water_at(x, y))
+}
+
+fn segment_tiles(from: (i32, i32), to: (i32, i32)) -> impl Iterator<Item = (i32, i32)> {
+    let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs());
+    let dx = (to.0 - from.0).signum();
+    let dy = (to.1 - from.1).signum();
+    (0..=steps).map(move |step| (from.0 + dx * step, from.1 + dy * step))
+}
+
+fn polyline_tiles(points: &[(i32, i32)]) -> impl Iterator<Item = (i32, i32)> + '_ {
+    points.windows(2).flat_map(|segment| segment_tiles(segment[0], segment[1]))
+}
+
+// Z-образный путь между узлами: перебираем место поворота
+// и выбираем то, где дорога пересекает меньше всего воды
+fn route(generator: &TerrainGenerator, from: (i32, i32), to: (i32, i32), horizontal: bool) -> Vec<(i32, i32)> {
+    let polyline = |turn: i32| {
+        if horizontal {
+            vec![from, (turn, from.1), (turn, to.1), to]
+        } else {
+            vec![from, (from.0, turn), (to.0, turn), to]
+        }
+    };
+
+    let (start, end) = if horizontal { (from.0, to.0) } else { (from.1, to.1) };
+    let middle = (start + end) / 2;
+    let mut turns: Vec<i32> = (start.min(end)..=start.max(end)).step_by(ROUTE_STEP as usize).collect();
+    turns.push(end);
+
+    let best = turns
+        .into_iter()
+        .min_by_key(|&turn| {
+            let water = polyline_tiles(&polyline(turn))
+                .filter(|&(x, y)| generator.is_water_at(x, y))
+                .count();
+            (water, (turn - middle).abs())
+        })
+        .unwrap_or(middle);
+
+    polyline(best)
+}
+
+// Рёбра, которые могут пройти через прямоугольник min..=max
+fn edges_near(generator: &TerrainGenerator, min: (i32, i32), max: (i32, i32)) -> Vec<RoadEdge> {
+    let region_min = (min.0.div_euclid(REGION_SIZE), min.1.div_euclid(REGION_SIZE));
+    let region_max = (max.0.div_euclid(REGION_SIZE), max.1.div_euclid(REGION_SIZE));
+
+    let mut edges = Vec::new();
+    for region_y in region_min.1 - 1..=region_max.1 {
+        for region_x in region_min.0 - 1..=region_max.0 {
+            let region = (region_x, region_y);
+            let Some(from) = road_node(generator, region) else {
+                continue;
+            };
+
+            // Каждый узел соединяется с восточным и северным соседом
+            for (salt, horizontal) in [(100, true), (101, false)] {
+                let neighbour = if horizontal { (region_x + 1, region_y) } else { (region_x, region_y + 1) };
+                let Some(to) = road_node(generator, neighbour) else {
+                    continue;
+                };
+
+                // Путь не выходит за прямоугольник, натянутый на узлы
+                let outside = from.0.max(to.0) < min.0
+                    || from.0.min(to.0) > max.0
+                    || from.1.max(to.1) < min.1
+                    || from.1.min(to.1) > max.1;
+                if outside {
+                    continue;
+                }
+
+                edges.push(RoadEdge {
+                    points: generator
+                        .road_cache()
+                        .get_or_insert_with((from, to), || route(generator, from, to, horizontal)),
+                    variant: (grid_hash(generator.seed(), region, salt) & 1) as u8,
+                });
+            }
+        }
+    }
+
+    edges
+}
+
+fn piece_for(mask: u8) -> (RoadPiece, f32) {
+    // Тупик рисуется прямым участком вдоль единственного соседа
+    let mask = match mask {
+        EAST | WEST => EAST | WEST,
+        NORTH | SOUTH | 0 => NORTH | SOUTH,
+        mask => mask,
+    };
+
+    for (piece, canonical) in PIECES {
+        let mut rotated = canonical;
+        for quarter in 0..4 {
+            if rotated == mask {
+                return (piece, quarter as f32 * 90.0);
+            }
+            rotated = ((rotated << 1) | (rotated >> 3)) & 0b1111;
+        }
+    }
+
+    (RoadPiece::Crossing, 0.0)
+}
+
+// Дорожные тайлы чанка с учётом соседей за его краем
+pub fn road_tiles(generator: &TerrainGenerator, chunk_pos: ChunkPosition) -> HashMap<(i32, i32), RoadTile> {
+    let min = (chunk_pos.0 * CHUNK_SIZE - 1, chunk_pos.1 * CHUNK_SIZE - 1);
+    let max = (min.0 + CHUNK_SIZE + 1, min.1 + CHUNK_SIZE + 1);
+    let inside = |(x, y): (i32, i32)| x >= min.0 && x <= max.0 && y >= min.1 && y <= max.1;
+
+    let mut road = HashMap::new();
+    for edge in edges_near(generator, min, max) {
+        // Вода на пути дороги становится мостом, так что дорога не рвётся ни на реке, ни на озере
+        for tile in polyline_tiles(&edge.points).filter(|&tile| inside(tile)) {
+            road.entry(tile).or_insert(edge.variant);
+        }
+    }
+
+    let mut tiles = HashMap::new();
+    for (&(x, y), &variant) in &road {
+        let in_chunk = x > min.0 && x < max.0 && y > min.1 && y < max.1;
+        if !in_chunk {
+            continue;
+        }
+
+        let mut mask = 0;
+        for (bit, (dx, dy)) in [(EAST, (1, 0)), (NORTH, (0, 1)), (WEST, (-1, 0)), (SOUTH, (0, -1))] {
+            if road.contains_key(&(x + dx, y + dy)) {
+                mask |= bit;
+            }
+        }
+
+        let (piece, rotation) = piece_for(mask);
+        tiles.insert((x, y), RoadTile { piece, rotation, variant });
+    }
+
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::terrain::TerrainSettings;
+
+    const SEED: u64 = 12345;
+    // Квадрат чанков, в который попадает несколько регионов дорог
+    const AREA: i32 = 14;
+
+    fn road_area(generator: &TerrainGenerator) -> HashMap<(i32, i32), RoadTile> {
+        let mut road = HashMap::new();
+        for chunk_y in -AREA / 2..AREA / 2 {
+            for chunk_x in -AREA / 2..AREA / 2 {
+                road.extend(road_tiles(generator, ChunkPosition(chunk_x, chunk_y)));
+            }
+        }
+        road
+    }
+
+    // Каждый чанк выбирает кусок дороги по соседям за своим краем,
+    // так что собранная из чанков дорога должна совпасть с тем, что видел каждый из них
+    #[test]
+    fn road_continues_into_neighbouring_chunk() {
+        let generator = TerrainGenerator::new(SEED, TerrainSettings::default());
+        let road = road_area(&generator);
+
+        let mut crossings = 0;
+        for (&(x, y), tile) in &road {
+            let mut mask = 0;
+            for (bit, (dx, dy)) in [(EAST, (1, 0)), (NORTH, (0, 1)), (WEST, (-1, 0)), (SOUTH, (0, -1))] {
+                if road.contains_key(&(x + dx, y + dy)) {
+                    mask |= bit;
+                }
+            }
+
+            // Край области никто не продолжает, его не проверяем
+            let last = AREA / 2 * CHUNK_SIZE - 1;
+            let first = -AREA / 2 * CHUNK_SIZE;
+            if x == first || x == last || y == first || y == last {
+                continue;
+            }
+            assert_eq!(piece_for(mask), (tile.piece, tile.rotation), "дорога рвётся на ({}, {})", x, y);
+
+            if x.rem_euclid(CHUNK_SIZE) == CHUNK_SIZE - 1 && mask & EAST != 0 {
+                crossings += 1;
+            }
+        }
+        assert!(crossings > 0, "ни одна дорога не пересекает край чанка");
+    }
+
+    // На пути дороги нет пропусков, в том числе там, где она идёт по воде
+    #[test]
+    fn road_has_no_gaps() {
+        let generator = TerrainGenerator::new(SEED, TerrainSettings::default());
+        let road = road_area(&generator);
+        let min = (-AREA / 2 * CHUNK_SIZE, -AREA / 2 * CHUNK_SIZE);
+        let max = (AREA / 2 * CHUNK_SIZE - 1, AREA / 2 * CHUNK_SIZE - 1);
+
+        let mut edges = 0;
+        for edge in edges_near(&generator, min, max) {
+            for (x, y) in polyline_tiles(&edge.points) {
+                if x >= min.0 && x <= max.0 && y >= min.1 && y <= max.1 {
+                    assert!(road.contains_key(&(x, y)), "нет дороги на ({}, {})", x, y);
+                    edges += 1;
+                }
+            }
+        }
+        assert!(edges > 0, "в проверяемой области нет дорог");
+    }
+}
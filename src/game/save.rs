@@ -0,0 +1,267 @@
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::game::building::Structure;
+use crate::game::chunk_store::ChunkStore;
+use crate::game::clock::WorldClock;
+use crate::game::data::saves_dir;
+use crate::game::debug::TileBenchmark;
+use crate::game::health::{Dead, Health, SpawnPoint};
+use crate::game::inventory::{Inventory, WorldItem};
+use crate::game::item::ItemRegistry;
+use crate::game::map::{ChunkPersistence, Decoration, MapState};
+use crate::game::player::Player;
+use crate::game::station::CraftingStation;
+use crate::game::survival::Survival;
+
+// Версия save.ron. Новые поля добавляются с #[serde(default)], чтобы старые сохранения читались
+const SAVE_VERSION: u32 = 1;
+const SAVE_FILE: &str = "save.ron";
+const DEFAULT_SLOT: &str = "default";
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(300);
+
+// Изменённые чанки лежат рядом, в каталоге chunks слота, вместе с постройками, станциями и предметами на земле.
+// Враги не сохраняются: после загрузки они появляются заново по таблицам биомов
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub version: u32,
+    pub seed: u64,
+    // Мировое время в секундах
+    #[serde(default)]
+    pub time: f64,
+    pub player: PlayerSave,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PlayerSave {
+    pub position: (f32, f32),
+    // None — полное здоровье
+    #[serde(default)]
+    pub health: Option<f32>,
+    #[serde(default)]
+    pub bed: Option<(f32, f32)>,
+    #[serde(default)]
+    pub survival: Option<Survival>,
+    // Слоты инвентаря: имя предмета и количество
+    #[serde(default)]
+    pub inventory: Vec<Option<(String, u32)>>,
+}
+
+#[derive(Event)]
+pub struct SaveGame;
+
+#[derive(Event)]
+pub struct LoadGame {
+    pub slot: String,
+}
+
+// Текущий слот и список слотов на диске для меню
+#[derive(Resource)]
+pub struct SaveSlots {
+    pub current: String,
+    pub available: Vec<String>,
+}
+
+#[derive(Resource)]
+pub struct AutosaveTimer(Timer);
+
+fn slot_dir(slot: &str) -> PathBuf {
+    saves_dir().join(slot)
+}
+
+fn chunks_dir(slot: &str) -> PathBuf {
+    slot_dir(slot).join("chunks")
+}
+
+// Чанки, выгруженные после последнего сохранения
+fn unsaved_chunks_dir(slot: &str) -> PathBuf {
+    slot_dir(slot).join("unsaved_chunks")
+}
+
+// Слоты — каталоги внутри saves, в которых есть save.ron
+fn list_slots() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(saves_dir()) else {
+        return Vec::new();
+    };
+
+    let mut slots: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join(SAVE_FILE).is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    slots.sort();
+    slots
+}
+
+fn read_save(dir: &Path) -> Result<Option<SaveFile>, String> {
+    let path = dir.join(SAVE_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let text = fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let save: SaveFile = ron::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+    if save.version > SAVE_VERSION {
+        return Err(format!("{}: сохранение из более новой версии игры ({})", path.display(), save.version));
+    }
+    Ok(Some(save))
+}
+
+fn write_save(dir: &Path, save: &SaveFile) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+
+    let text = ron::ser::to_string_pretty(save, ron::ser::PrettyConfig::default()).map_err(|err| err.to_string())?;
+    let path = dir.join(SAVE_FILE);
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, text).map_err(|err| format!("{}: {}", temp.display(), err))?;
+    fs::rename(&temp, &path).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+// Слот можно выбрать первым аргументом командной строки
+pub fn setup_save(mut commands: Commands, mut load_events: EventWriter<LoadGame>) {
+    let current = std::env::args().nth(1).unwrap_or_else(|| DEFAULT_SLOT.to_string());
+
+    commands.insert_resource(ChunkStore::new(chunks_dir(&current), unsaved_chunks_dir(&current)));
+    commands.insert_resource(AutosaveTimer(Timer::new(AUTOSAVE_INTERVAL, TimerMode::Repeating)));
+    commands.insert_resource(SaveSlots {
+        current: current.clone(),
+        available: list_slots(),
+    });
+    load_events.send(LoadGame { slot: current });
+}
+
+pub fn autosave(
+    time: Res<Time>,
+    mut timer: ResMut<AutosaveTimer>,
+    mut save_events: EventWriter<SaveGame>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        save_events.send(SaveGame);
+    }
+}
+
+// Состояние игрока, которое попадает в save.ron
+#[derive(SystemParam)]
+pub struct PlayerPersistence<'w, 's> {
+    spawn_point: ResMut<'w, SpawnPoint>,
+    items: Res<'w, ItemRegistry>,
+    player_query: Query<
+        'w,
+        's,
+        (Entity, &'static mut Transform, &'static mut Health, &'static mut Survival, &'static mut Inventory),
+        (With<Player>, Without<Decoration>, Without<Structure>, Without<WorldItem>, Without<CraftingStation>),
+    >,
+}
+
+impl PlayerPersistence<'_, '_> {
+    fn save(&self) -> PlayerSave {
+        let Ok((_, transform, health, survival, inventory)) = self.player_query.get_single() else {
+            return PlayerSave::default();
+        };
+
+        PlayerSave {
+            position: (transform.translation.x, transform.translation.y),
+            health: Some(health.current),
+            bed: self.spawn_point.bed.map(|bed| (bed.x, bed.y)),
+            survival: Some(*survival),
+            inventory: inventory.to_save(&self.items),
+        }
+    }
+
+    fn load(&mut self, commands: &mut Commands, save: &PlayerSave) {
+        self.spawn_point.reset(save.bed.map(|(x, y)| Vec2::new(x, y)));
+
+        let Ok((entity, mut transform, mut health, mut survival, mut inventory)) = self.player_query.get_single_mut() else {
+            return;
+        };
+        transform.translation.x = save.position.0;
+        transform.translation.y = save.position.1;
+        // Сохранение, сделанное после смерти, загружается с полным здоровьем
+        health.current = save.health.filter(|health| *health > 0.0).unwrap_or(health.max);
+        *survival = save.survival.unwrap_or_default();
+        inventory.load_save(&self.items, &save.inventory);
+        commands.entity(entity).remove::<Dead>();
+    }
+}
+
+pub fn save_game(
+    mut save_events: EventReader<SaveGame>,
+    mut slots: ResMut<SaveSlots>,
+    map_state: Res<MapState>,
+    mut persistence: ChunkPersistence,
+    clock: Res<WorldClock>,
+    player: PlayerPersistence,
+    benchmark: Option<Res<TileBenchmark>>,
+) {
+    if save_events.is_empty() {
+        return;
+    }
+    save_events.clear();
+
+    // Во время замера тайлов открыт мир замера, и его сид с позицией игрока затёрли бы слот
+    if benchmark.is_some() {
+        println!("Во время замера тайлов игра не сохраняется");
+        return;
+    }
+
+    // Сначала чанки, потом save.ron: слот без нового save.ron всё равно загрузится
+    if let Err(err) = persistence.commit(&map_state) {
+        println!("Ошибка сохранения: {}", err);
+        return;
+    }
+
+    let save = SaveFile {
+        version: SAVE_VERSION,
+        seed: map_state.seed(),
+        time: clock.elapsed,
+        player: player.save(),
+    };
+
+    match write_save(&slot_dir(&slots.current), &save) {
+        Ok(()) => {
+            println!("Игра сохранена в слот {}", slots.current);
+            slots.available = list_slots();
+        }
+        Err(err) => println!("Ошибка сохранения: {}", err),
+    }
+}
+
+// Загружает слот, а если его ещё нет — начинает в нём новый мир
+pub fn load_game(
+    mut commands: Commands,
+    mut load_events: EventReader<LoadGame>,
+    mut slots: ResMut<SaveSlots>,
+    mut map_state: ResMut<MapState>,
+    mut persistence: ChunkPersistence,
+    mut clock: ResMut<WorldClock>,
+    mut player: PlayerPersistence,
+) {
+    let Some(slot) = load_events.iter().last().map(|event| event.slot.clone()) else {
+        return;
+    };
+
+    let save = match read_save(&slot_dir(&slot)) {
+        Ok(Some(save)) => save,
+        Ok(None) => SaveFile {
+            version: SAVE_VERSION,
+            seed: rand::random(),
+            time: 0.0,
+            player: PlayerSave::default(),
+        },
+        Err(err) => {
+            println!("Ошибка загрузки слота {}: {}", slot, err);
+            return;
+        }
+    };
+
+    map_state.reset(&mut commands, save.seed);
+    persistence.open(chunks_dir(&slot), unsaved_chunks_dir(&slot));
+    clock.elapsed = save.time;
+    player.load(&mut commands, &save.player);
+
+    println!("Загружен слот {}", slot);
+    slots.current = slot;
+}
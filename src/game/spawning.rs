@@ -0,0 +1,166 @@
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use rand::Rng;
+use crate::game::clock::WorldClock;
+use crate::game::enemy::{Enemy, EnemyRegistry, EnemySpawner};
+use crate::game::map::{MapState, SimulatedChunk};
+use crate::game::player::Player;
+use crate::game::tile_query::TileQuery;
+use crate::game::world::{ChunkPosition, TilePos, CHUNK_SIZE, TILE_SIZE};
+
+// Как часто делается попытка кого-нибудь заспавнить, в секундах
+const SPAWN_INTERVAL: f32 = 2.0;
+// Ближе этого к игроку враги не появляются, в тайлах
+const MIN_SPAWN_DISTANCE: f32 = 12.0;
+// Сколько врагов может жить на один чанк в радиусе симуляции. Ночью их больше
+const DAY_MOBS_PER_CHUNK: f32 = 0.15;
+const NIGHT_MOBS_PER_CHUNK: f32 = 0.35;
+// Враги из одной группы появляются вокруг первого в этом радиусе, в тайлах
+const GROUP_SPREAD: f32 = 1.5;
+
+#[derive(Resource)]
+pub struct MobSpawner {
+    timer: Timer,
+}
+
+// Загруженный мир с точки зрения спавна: тайлы, чанки в радиусе симуляции и виды врагов
+#[derive(SystemParam)]
+pub struct SpawnArea<'w, 's> {
+    tiles: TileQuery<'w, 's>,
+    enemies: EnemySpawner<'w>,
+    simulated_query: Query<'w, 's, (), With<SimulatedChunk>>,
+}
+
+impl SpawnArea<'_, '_> {
+    fn map_state(&self) -> &MapState {
+        self.tiles.map_state()
+    }
+
+    fn simulated_chunks(&self) -> Vec<ChunkPosition> {
+        self.map_state()
+            .loaded_chunks()
+            .filter(|(_, entity)| self.simulated_query.contains(*entity))
+            .map(|(chunk_pos, _)| chunk_pos)
+            .collect()
+    }
+}
+
+// Таблицы появления ссылаются на врагов, поэтому проверяются после загрузки обоих реестров
+pub fn setup_spawning(mut commands: Commands, map_state: Res<MapState>, enemies: Res<EnemyRegistry>) {
+    for biome in map_state.biomes().iter() {
+        if let Some(spawn) = biome.spawns.iter().find(|spawn| enemies.find(&spawn.enemy).is_none()) {
+            panic!("Не удалось загрузить биомы: биом {}: неизвестный враг {}", biome.name, spawn.enemy);
+        }
+    }
+
+    commands.insert_resource(MobSpawner {
+        timer: Timer::from_seconds(SPAWN_INTERVAL, TimerMode::Repeating),
+    });
+}
+
+// Сколько врагов может жить сразу. Хотя бы один чанк в радиусе симуляции - хотя бы один враг
+fn mob_cap(simulated_chunks: usize, night: bool) -> usize {
+    let density = if night { NIGHT_MOBS_PER_CHUNK } else { DAY_MOBS_PER_CHUNK };
+    (simulated_chunks as f32 * density).ceil() as usize
+}
+
+// Группа урезается так, чтобы живых врагов не стало больше предела
+fn group_size(group: usize, cap: usize, alive: usize) -> usize {
+    group.min(cap.saturating_sub(alive))
+}
+
+// Время от времени выбирает случайный тайл в радиусе симуляции и ставит на него врагов из таблицы его биома
+pub fn spawn_mobs(
+    mut commands: Commands,
+    time: Res<Time>,
+    clock: Res<WorldClock>,
+    mut spawner: ResMut<MobSpawner>,
+    area: SpawnArea,
+    player_query: Query<&Transform, With<Player>>,
+    enemy_query: Query<(), With<Enemy>>,
+) {
+    if !spawner.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player = player_transform.translation.truncate();
+    let night = clock.is_night();
+
+    let chunks = area.simulated_chunks();
+    let cap = mob_cap(chunks.len(), night);
+    let alive = enemy_query.iter().count();
+    if chunks.is_empty() || alive >= cap {
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    let chunk_pos = chunks[rng.gen_range(0..chunks.len())];
+    let tile = chunk_pos.tile(rng.gen_range(0..CHUNK_SIZE), rng.gen_range(0..CHUNK_SIZE));
+    let center = tile.to_world();
+    if center.distance(player) < MIN_SPAWN_DISTANCE * TILE_SIZE {
+        return;
+    }
+    let Some(info) = area.tiles.tile(tile).filter(|info| info.walkable) else {
+        return;
+    };
+    let Some(spawn) = area.map_state().biomes().get(info.biome).pick_spawn(night, rng.gen()) else {
+        return;
+    };
+    let Some(kind) = area.enemies.registry().find(&spawn.enemy) else {
+        println!("Неизвестный враг в таблице появления: {}", spawn.enemy);
+        return;
+    };
+
+    let group = rng.gen_range(spawn.group.0..=spawn.group.1) as usize;
+    for index in 0..group_size(group, cap, alive) {
+        let offset = if index == 0 {
+            Vec2::ZERO
+        } else {
+            Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)) * GROUP_SPREAD * TILE_SIZE
+        };
+        let position = center + offset;
+        if area.tiles.is_walkable(TilePos::from_world(position)) {
+            area.enemies.spawn(&mut commands, kind, position);
+        }
+    }
+}
+
+// Враг принадлежит чанку, в котором стоит, и выгружается вместе с ним.
+// Ушедший за пределы загруженных чанков враг пропадает сразу
+pub fn update_mob_chunks(
+    mut commands: Commands,
+    map_state: Res<MapState>,
+    enemy_query: Query<(Entity, &Transform, Option<&Parent>), With<Enemy>>,
+) {
+    for (entity, transform, parent) in enemy_query.iter() {
+        let chunk_pos = ChunkPosition::from_world(transform.translation.truncate());
+        match map_state.chunk_entity(chunk_pos) {
+            Some(chunk) if parent.map(|parent| parent.get()) != Some(chunk) => {
+                commands.entity(entity).set_parent(chunk);
+            }
+            Some(_) => {}
+            None => commands.entity(entity).despawn_recursive(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cap_grows_with_chunks_and_night() {
+        assert_eq!(mob_cap(0, false), 0, "без чанков врагов нет");
+        assert_eq!(mob_cap(1, false), 1, "на одном чанке живёт хотя бы один враг");
+        assert!(mob_cap(25, true) > mob_cap(25, false), "ночью врагов больше");
+    }
+
+    #[test]
+    fn group_is_clamped_by_cap() {
+        assert_eq!(group_size(3, 10, 2), 3, "далеко от предела группа появляется целиком");
+        assert_eq!(group_size(3, 10, 8), 2, "у предела группа урезается");
+        assert_eq!(group_size(3, 10, 12), 0, "сверх предела никто не появляется");
+    }
+}
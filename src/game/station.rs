@@ -0,0 +1,222 @@
+use std::path::Path;
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use serde::Deserialize;
+use crate::game::building::BuildSite;
+use crate::game::bullet::CursorPosition;
+use crate::game::collision::Collider;
+use crate::game::data::load_ron_dir;
+use crate::game::health::{Dead, SpawnPoint};
+use crate::game::inventory::Inventory;
+use crate::game::item::{ItemRegistry, ItemType};
+use crate::game::map::{ChunkModified, YSort};
+use crate::game::menu::GameState;
+use crate::game::player::Player;
+use crate::game::structure::StructureLayer;
+use crate::game::survival::HeatSource;
+use crate::game::world::{TilePos, TILE_SIZE};
+
+// Индекс станции в реестре, станции сортируются по имени
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StationType(pub u16);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Warmth {
+    // Радиус в тайлах
+    pub radius: f32,
+    // Окружающая температура рядом со станцией, в единицах климата биома
+    pub temperature: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StationDefinition {
+    pub name: String,
+    pub title: String,
+    // Предмет, которым станцию ставят
+    pub item: String,
+    pub texture: String,
+    // На каком расстоянии от станции, в тайлах, можно ей пользоваться
+    pub range: f32,
+    #[serde(default)]
+    pub collision_radius: Option<f32>,
+    #[serde(default)]
+    pub warmth: Option<Warmth>,
+    // В кровати (Q рядом с ней) игрок назначает себе точку возрождения
+    #[serde(default)]
+    pub bed: bool,
+    #[serde(skip)]
+    pub item_type: ItemType,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct StationRegistry {
+    stations: Vec<StationDefinition>,
+}
+
+impl StationRegistry {
+    pub fn load(dir: &Path, items: &ItemRegistry) -> Result<Self, String> {
+        Self::from_definitions(load_ron_dir(dir)?, items)
+    }
+
+    pub fn from_definitions(mut stations: Vec<StationDefinition>, items: &ItemRegistry) -> Result<Self, String> {
+        if stations.len() > u16::MAX as usize {
+            return Err(format!("слишком много станций: {}", stations.len()));
+        }
+        stations.sort_by(|a, b| a.name.cmp(&b.name));
+
+        if let Some(pair) = stations.windows(2).find(|pair| pair[0].name == pair[1].name) {
+            return Err(format!("станция {} объявлена дважды", pair[0].name));
+        }
+        for station in &mut stations {
+            station.item_type = items
+                .find(&station.item)
+                .ok_or_else(|| format!("станция {}: неизвестный предмет {}", station.name, station.item))?;
+        }
+
+        Ok(Self { stations })
+    }
+
+    pub fn get(&self, station: StationType) -> &StationDefinition {
+        &self.stations[station.0 as usize]
+    }
+
+    pub fn find(&self, name: &str) -> Option<StationType> {
+        self.stations
+            .binary_search_by(|station| station.name.as_str().cmp(name))
+            .ok()
+            .map(|index| StationType(index as u16))
+    }
+
+    // Станция, которую ставит предмет
+    pub fn find_item(&self, item: ItemType) -> Option<StationType> {
+        self.stations
+            .iter()
+            .position(|station| station.item_type == item)
+            .map(|index| StationType(index as u16))
+    }
+}
+
+#[derive(Component)]
+pub struct CraftingStation {
+    pub kind: StationType,
+}
+
+// Станции, до которых игрок в точке position может дотянуться
+pub fn nearby_stations<'a>(
+    registry: &StationRegistry,
+    stations: impl Iterator<Item = (&'a GlobalTransform, &'a CraftingStation)>,
+    position: Vec2,
+) -> Vec<StationType> {
+    stations
+        .filter(|(transform, station)| {
+            let range = registry.get(station.kind).range * TILE_SIZE;
+            transform.translation().truncate().distance(position) <= range
+        })
+        .map(|(_, station)| station.kind)
+        .collect()
+}
+
+pub fn spawn_station(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    registry: &StationRegistry,
+    kind: StationType,
+    position: Vec2,
+) -> Entity {
+    let definition = registry.get(kind);
+    let mut entity = commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_xyz(position.x, position.y, 1.0),
+            texture: asset_server.load(definition.texture.as_str()),
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(TILE_SIZE)),
+                ..default()
+            },
+            ..default()
+        },
+        CraftingStation { kind },
+        YSort,
+    ));
+
+    if let Some(radius) = definition.collision_radius {
+        entity.insert(Collider { radius: radius * TILE_SIZE });
+    }
+    if let Some(warmth) = &definition.warmth {
+        entity.insert(HeatSource {
+            radius: warmth.radius * TILE_SIZE,
+            temperature: warmth.temperature,
+        });
+    }
+    entity.id()
+}
+
+// Всё, что нужно, чтобы поставить станцию из инвентаря
+#[derive(SystemParam)]
+pub struct StationPlacement<'w, 's> {
+    registry: Res<'w, StationRegistry>,
+    cursor: CursorPosition<'w, 's>,
+    site: BuildSite<'w, 's>,
+}
+
+impl StationPlacement<'_, '_> {
+    pub fn station(&self, item: ItemType) -> Option<StationType> {
+        self.registry.find_item(item)
+    }
+
+    // Ставит станцию из выбранного слота на тайл под курсором. Станция принадлежит чанку
+    // и сохраняется вместе с ним, как постройки
+    pub fn place(&self, commands: &mut Commands, kind: StationType, inventory: &mut Inventory, player: Vec2) {
+        let Some(cursor) = self.cursor.world_position() else {
+            return;
+        };
+        let tile = TilePos::from_world(cursor);
+        let definition = self.registry.get(kind);
+
+        // Станция без столкновений тоже не ставится внутрь деревьев, камней и живых
+        let radius = definition.collision_radius.unwrap_or(0.0);
+        if let Err(err) = self.site.check_place(tile, player, StructureLayer::Object, Some(radius)) {
+            println!("Нельзя поставить {}: {}", definition.title, err);
+            return;
+        }
+        let Some(chunk) = self.site.map_state().chunk_entity(tile.chunk()) else {
+            return;
+        };
+
+        let selected = inventory.selected;
+        inventory.take(selected, 1);
+        let entity = spawn_station(commands, self.site.asset_server(), &self.registry, kind, tile.to_world());
+        commands.entity(entity).set_parent(chunk);
+        commands.entity(chunk).insert(ChunkModified);
+    }
+}
+
+// Q рядом с кроватью: после смерти игрок появится у неё. На E пьют воду, а она может оказаться рядом
+pub fn use_bed(
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    registry: Res<StationRegistry>,
+    mut spawn_point: ResMut<SpawnPoint>,
+    station_query: Query<(&GlobalTransform, &CraftingStation)>,
+    player_query: Query<&Transform, (With<Player>, Without<Dead>)>,
+) {
+    if game_state.paused || !keyboard.just_pressed(KeyCode::Q) {
+        return;
+    }
+    let Ok(transform) = player_query.get_single() else {
+        return;
+    };
+    let position = transform.translation.truncate();
+
+    let bed = station_query
+        .iter()
+        .filter(|(_, station)| registry.get(station.kind).bed)
+        .map(|(transform, station)| (transform.translation().truncate(), registry.get(station.kind).range * TILE_SIZE))
+        .filter(|(bed, range)| bed.distance(position) <= *range)
+        .min_by(|(a, _), (b, _)| a.distance(position).total_cmp(&b.distance(position)));
+    if let Some((bed, _)) = bed {
+        if spawn_point.bed != Some(bed) {
+            println!("Точка возрождения: кровать ({:.0}, {:.0})", bed.x, bed.y);
+        }
+        spawn_point.bed = Some(bed);
+    }
+}
@@ -0,0 +1,165 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::game::data::load_ron_dir;
+use crate::game::item::{ItemRegistry, ItemStack};
+
+// Индекс постройки в реестре, постройки сортируются по имени
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StructureType(pub u16);
+
+// На одном тайле может стоять по одной постройке каждого слоя
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum StructureLayer {
+    // Пол лежит под всем остальным и не мешает ходить
+    Floor,
+    // Стены и всё, что стоит на тайле
+    Object,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StructureDefinition {
+    pub name: String,
+    pub title: String,
+    pub texture: String,
+    pub layer: StructureLayer,
+    // Имя предмета и количество
+    pub cost: Vec<(String, u32)>,
+    pub health: f32,
+    // Какая доля стоимости возвращается при разборке целой постройки
+    #[serde(default = "default_refund")]
+    pub refund: f32,
+    #[serde(default)]
+    pub collision_radius: Option<f32>,
+}
+
+fn default_refund() -> f32 {
+    0.5
+}
+
+impl StructureDefinition {
+    pub fn cost(&self, items: &ItemRegistry) -> Result<Vec<ItemStack>, String> {
+        self.cost
+            .iter()
+            .map(|(name, count)| {
+                let item = items
+                    .find(name)
+                    .ok_or_else(|| format!("постройка {}: неизвестный предмет {}", self.name, name))?;
+                Ok(ItemStack { item, count: *count })
+            })
+            .collect()
+    }
+
+    // Что вернётся при разборке. Повреждённая постройка возвращает меньше
+    pub fn refund(&self, items: &ItemRegistry, health: f32) -> Result<Vec<ItemStack>, String> {
+        let share = self.refund * (health / self.health).clamp(0.0, 1.0);
+        Ok(self
+            .cost(items)?
+            .into_iter()
+            .map(|stack| ItemStack { item: stack.item, count: (stack.count as f32 * share).floor() as u32 })
+            .filter(|stack| stack.count > 0)
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StructureRegistry {
+    structures: Vec<StructureDefinition>,
+}
+
+impl StructureRegistry {
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        Self::from_definitions(load_ron_dir(dir)?)
+    }
+
+    pub fn from_definitions(mut structures: Vec<StructureDefinition>) -> Result<Self, String> {
+        if structures.len() > u16::MAX as usize {
+            return Err(format!("слишком много построек: {}", structures.len()));
+        }
+        structures.sort_by(|a, b| a.name.cmp(&b.name));
+
+        if let Some(pair) = structures.windows(2).find(|pair| pair[0].name == pair[1].name) {
+            return Err(format!("постройка {} объявлена дважды", pair[0].name));
+        }
+        if let Some(structure) = structures.iter().find(|structure| structure.health <= 0.0) {
+            return Err(format!("постройка {}: прочность должна быть больше нуля", structure.name));
+        }
+
+        Ok(Self { structures })
+    }
+
+    pub fn get(&self, structure: StructureType) -> &StructureDefinition {
+        &self.structures[structure.0 as usize]
+    }
+
+    pub fn count(&self) -> usize {
+        self.structures.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StructureDefinition> {
+        self.structures.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::item::test_item;
+
+    fn items() -> ItemRegistry {
+        let item = |name| test_item(name, 20, 1.0);
+        ItemRegistry::from_definitions(vec![item("wood"), item("stone")]).unwrap()
+    }
+
+    fn wall() -> StructureDefinition {
+        StructureDefinition {
+            name: "wall".to_string(),
+            title: "wall".to_string(),
+            texture: String::new(),
+            layer: StructureLayer::Object,
+            cost: vec![("wood".to_string(), 5), ("stone".to_string(), 1)],
+            health: 100.0,
+            refund: default_refund(),
+            collision_radius: None,
+        }
+    }
+
+    fn counts(items: &ItemRegistry, stacks: &[ItemStack]) -> Vec<(String, u32)> {
+        stacks.iter().map(|stack| (items.get(stack.item).name.clone(), stack.count)).collect()
+    }
+
+    // Доля округляется вниз, а стопки, от которых ничего не осталось, не возвращаются
+    #[test]
+    fn refund_at_full_health() {
+        let items = items();
+        let refund = wall().refund(&items, 100.0).unwrap();
+        assert_eq!(counts(&items, &refund), [("wood".to_string(), 2)]);
+    }
+
+    #[test]
+    fn refund_at_partial_health() {
+        let items = items();
+        let mut wall = wall();
+        wall.refund = 1.0;
+        assert_eq!(counts(&items, &wall.refund(&items, 60.0).unwrap()), [("wood".to_string(), 3)]);
+        assert_eq!(counts(&items, &wall.refund(&items, 19.0).unwrap()), Vec::<(String, u32)>::new());
+    }
+
+    #[test]
+    fn refund_health_is_clamped() {
+        let items = items();
+        let mut wall = wall();
+        wall.refund = 1.0;
+        assert_eq!(
+            counts(&items, &wall.refund(&items, 250.0).unwrap()),
+            [("wood".to_string(), 5), ("stone".to_string(), 1)]
+        );
+        assert!(wall.refund(&items, -10.0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn refund_with_unknown_item_fails() {
+        let mut wall = wall();
+        wall.cost.push(("iron".to_string(), 1));
+        assert!(wall.refund(&items(), 100.0).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::game::generate_map::TileType;
+use crate::game::health::{DamageEvent, DamageSource, Dead, RespawnPlayer};
+use crate::game::menu::GameState;
+use crate::game::player::Player;
+use crate::game::tile_query::TileQuery;
+use crate::game::world::TilePos;
+
+const MAX_NEED: f32 = 100.0;
+// Скорость убывания сытости и воды, единиц в секунду
+const HUNGER_RATE: f32 = MAX_NEED / 600.0;
+const THIRST_RATE: f32 = MAX_NEED / 400.0;
+// Температура тела в тех же единицах, что и климат биома (0..1)
+const COMFORT_TEMPERATURE: f32 = 0.5;
+// Доля разницы с окружающей температурой, которую тело догоняет за секунду
+const TEMPERATURE_DRIFT: f32 = 0.02;
+// Ниже этой температуры тело замерзает
+const COLD_THRESHOLD: f32 = 0.35;
+// Урон в секунду от холода и от пустых голода или жажды
+const COLD_DAMAGE: f32 = 1.0;
+const STARVATION_DAMAGE: f32 = 1.0;
+const DEHYDRATION_DAMAGE: f32 = 1.5;
+const DRINK_AMOUNT: f32 = 25.0;
+
+#[derive(Component, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Survival {
+    // 100 — сыт, 0 — умирает от голода
+    pub hunger: f32,
+    // 100 — напился, 0 — умирает от жажды
+    pub thirst: f32,
+    pub temperature: f32,
+}
+
+impl Default for Survival {
+    fn default() -> Self {
+        Self {
+            hunger: MAX_NEED,
+            thirst: MAX_NEED,
+            temperature: COMFORT_TEMPERATURE,
+        }
+    }
+}
+
+impl Survival {
+    pub fn eat(&mut self, amount: f32) {
+        self.hunger = (self.hunger + amount).min(MAX_NEED);
+    }
+
+    pub fn drink(&mut self, amount: f32) {
+        self.thirst = (self.thirst + amount).min(MAX_NEED);
+    }
+
+    pub fn freezing(&self) -> bool {
+        self.temperature < COLD_THRESHOLD
+    }
+
+    // Нужды и температура тела через delta секунд. ambient — температура вокруг, над незагруженным
+    // тайлом её нет, и температура тела не меняется. Возвращает урон от холода, голода и жажды за это время
+    pub fn tick(&mut self, delta: f32, ambient: Option<f32>) -> f32 {
+        self.hunger = (self.hunger - HUNGER_RATE * delta).max(0.0);
+        self.thirst = (self.thirst - THIRST_RATE * delta).max(0.0);
+
+        if let Some(ambient) = ambient {
+            let drift = 1.0 - (-TEMPERATURE_DRIFT * delta).exp();
+            self.temperature += (ambient - self.temperature) * drift;
+        }
+
+        let mut damage = 0.0;
+        if self.freezing() {
+            damage += COLD_DAMAGE;
+        }
+        if self.hunger <= 0.0 {
+            damage += STARVATION_DAMAGE;
+        }
+        if self.thirst <= 0.0 {
+            damage += DEHYDRATION_DAMAGE;
+        }
+        damage * delta
+    }
+}
+
+// Источник тепла: рядом с ним окружающая температура не ниже temperature
+#[derive(Component)]
+pub struct HeatSource {
+    pub radius: f32,
+    pub temperature: f32,
+}
+
+// Климат биома, поднятый до самого тёплого источника тепла, в радиус которого попадает position
+fn ambient_temperature<'a>(biome: f32, position: Vec2, heat: impl Iterator<Item = (Vec2, &'a HeatSource)>) -> f32 {
+    heat.filter(|(center, heat)| center.distance(position) <= heat.radius)
+        .map(|(_, heat)| heat.temperature)
+        .fold(biome, f32::max)
+}
+
+#[derive(Component)]
+pub struct SurvivalHud;
+
+pub fn setup_survival(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 24.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(40.0),
+            ..default()
+        }),
+        SurvivalHud,
+    ));
+}
+
+pub fn update_survival(
+    time: Res<Time>,
+    tiles: TileQuery,
+    heat_query: Query<(&GlobalTransform, &HeatSource)>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut player_query: Query<(Entity, &Transform, &mut Survival), (With<Player>, Without<Dead>)>,
+) {
+    let Ok((entity, transform, mut survival)) = player_query.get_single_mut() else {
+        return;
+    };
+    let position = transform.translation.truncate();
+
+    let ambient = tiles.biome(TilePos::from_world(position)).map(|biome| {
+        let heat = heat_query
+            .iter()
+            .map(|(heat_transform, heat)| (heat_transform.translation().truncate(), heat));
+        ambient_temperature(biome.temperature, position, heat)
+    });
+    let damage = survival.tick(time.delta_seconds(), ambient);
+    if damage > 0.0 {
+        damage_events.send(DamageEvent {
+            target: entity,
+            amount: damage,
+            source: DamageSource::Environment,
+        });
+    }
+}
+
+// Пить можно, стоя рядом с водой
+pub fn drink_water(
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    tiles: TileQuery,
+    mut player_query: Query<(&Transform, &mut Survival), (With<Player>, Without<Dead>)>,
+) {
+    if game_state.paused || !keyboard.just_pressed(KeyCode::E) {
+        return;
+    }
+    let Ok((transform, mut survival)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    let center = TilePos::from_world(transform.translation.truncate());
+    let near_water = (-1..=1).any(|dy| {
+        (-1..=1).any(|dx| {
+            tiles
+                .tile(center.offset(dx, dy))
+                .is_some_and(|tile| tile.tile_type == TileType::Water)
+        })
+    });
+    if near_water {
+        survival.drink(DRINK_AMOUNT);
+    }
+}
+
+pub fn reset_survival(
+    mut respawn_events: EventReader<RespawnPlayer>,
+    mut player_query: Query<&mut Survival, With<Player>>,
+) {
+    if respawn_events.is_empty() {
+        return;
+    }
+    respawn_events.clear();
+
+    for mut survival in player_query.iter_mut() {
+        *survival = Survival::default();
+    }
+}
+
+pub fn survival_hud(
+    player_query: Query<&Survival, With<Player>>,
+    mut hud_query: Query<&mut Text, With<SurvivalHud>>,
+) {
+    let (Ok(survival), Ok(mut text)) = (player_query.get_single(), hud_query.get_single_mut()) else {
+        return;
+    };
+
+    let mut value = format!(
+        "Сытость: {:.0}  Вода: {:.0}  Тепло: {:.0}%",
+        survival.hunger.ceil(),
+        survival.thirst.ceil(),
+        survival.temperature * 100.0,
+    );
+    if survival.freezing() {
+        value.push_str("  Холодно!");
+    }
+    text.sections[0].value = value;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hunger_and_thirst_decay() {
+        let mut survival = Survival::default();
+        let damage = survival.tick(10.0, None);
+        assert!((survival.hunger - (MAX_NEED - HUNGER_RATE * 10.0)).abs() < 1e-4);
+        assert!((survival.thirst - (MAX_NEED - THIRST_RATE * 10.0)).abs() < 1e-4);
+        assert_eq!(survival.temperature, COMFORT_TEMPERATURE, "без окружающей температуры тело не остывает");
+        assert_eq!(damage, 0.0);
+
+        let damage = survival.tick(1000.0, None);
+        assert_eq!((survival.hunger, survival.thirst), (0.0, 0.0), "нужды не уходят в минус");
+        assert_eq!(damage, (STARVATION_DAMAGE + DEHYDRATION_DAMAGE) * 1000.0);
+    }
+
+    #[test]
+    fn temperature_drifts_toward_biome() {
+        let mut survival = Survival::default();
+        survival.tick(1.0, Some(0.2));
+        assert!(survival.temperature < COMFORT_TEMPERATURE && survival.temperature > 0.2, "{}", survival.temperature);
+
+        for _ in 0..1000 {
+            survival.tick(1.0, Some(0.2));
+            survival.hunger = MAX_NEED;
+            survival.thirst = MAX_NEED;
+        }
+        assert!((survival.temperature - 0.2).abs() < 1e-3, "{}", survival.temperature);
+    }
+
+    #[test]
+    fn heat_source_warms_only_within_radius() {
+        let campfire = HeatSource { radius: 64.0, temperature: 0.7 };
+        let heat = [(Vec2::new(50.0, 0.0), &campfire)];
+
+        assert_eq!(ambient_temperature(0.1, Vec2::ZERO, heat.into_iter()), 0.7);
+        assert_eq!(ambient_temperature(0.1, Vec2::new(-50.0, 0.0), heat.into_iter()), 0.1);
+        assert_eq!(ambient_temperature(0.9, Vec2::ZERO, heat.into_iter()), 0.9, "тепло не охлаждает жаркий биом");
+
+        let mut survival = Survival { temperature: 0.1, ..default() };
+        survival.tick(1.0, Some(ambient_temperature(0.1, Vec2::ZERO, heat.into_iter())));
+        assert!(survival.temperature > 0.1, "у костра тело согревается");
+    }
+
+    #[test]
+    fn cold_damages_below_threshold() {
+        let mut survival = Survival { temperature: COLD_THRESHOLD - 0.01, ..default() };
+        assert_eq!(survival.tick(1.0, Some(COLD_THRESHOLD - 0.01)), COLD_DAMAGE);
+
+        let mut survival = Survival { temperature: COLD_THRESHOLD + 0.01, ..default() };
+        assert_eq!(survival.tick(1.0, Some(COLD_THRESHOLD + 0.01)), 0.0);
+    }
+}
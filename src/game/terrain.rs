@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+use crate::game::generate_map::splitmix64;
+use crate::game::rivers::RiverCache;
+use crate::game::roads::RoadCache;
+
+// Сколько результатов держит каждый кеш генератора. Этого с запасом хватает на регионы
+// вокруг загруженных чанков, вытесняются только давно пройденные места
+const GENERATION_CACHE_CAPACITY: usize = 256;
+
+// Параметры одного слоя шума (fBm поверх Perlin)
+#[derive(Debug, Clone, Copy)]
+pub struct NoiseLayerSettings {
+    pub octaves: usize,
+    pub frequency: f64,
+    pub lacunarity: f64,
+    pub persistence: f64,
+}
+
+impl NoiseLayerSettings {
+    fn build(&self, seed: u32) -> Fbm<Perlin> {
+        Fbm::<Perlin>::new(seed)
+            .set_octaves(self.octaves)
+            .set_frequency(self.frequency)
+            .set_lacunarity(self.lacunarity)
+            .set_persistence(self.persistence)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TerrainSettings {
+    pub elevation: NoiseLayerSettings,
+    pub temperature: NoiseLayerSettings,
+    pub humidity: NoiseLayerSettings,
+    // Всё, что ниже этой высоты, заливается водой
+    pub water_level: f32,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            elevation: NoiseLayerSettings {
+                octaves: 5,
+                frequency: 0.01,
+                lacunarity: 2.0,
+                persistence: 0.5,
+            },
+            temperature: NoiseLayerSettings {
+                octaves: 3,
+                frequency: 0.004,
+                lacunarity: 2.0,
+                persistence: 0.5,
+            },
+            humidity: NoiseLayerSettings {
+                octaves: 3,
+                frequency: 0.005,
+                lacunarity: 2.0,
+                persistence: 0.5,
+            },
+            water_level: 0.3,
+        }
+    }
+}
+
+// Значения всех слоёв в одной точке мира, нормированные в 0..1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainSample {
+    pub elevation: f32,
+    pub temperature: f32,
+    pub humidity: f32,
+}
+
+// Дорогие промежуточные результаты генерации, которые нужны многим чанкам подряд: пути дорог, русла рек.
+// Значение зависит только от сида и ключа, поэтому копии генератора в фоновых задачах делят один кеш
+// и считают значение без блокировки: если два потока посчитают одно и то же, ответы совпадут.
+// Сверх capacity выбрасываются записи, которые дольше всех не спрашивали
+#[derive(Debug, Clone)]
+pub struct GenerationCache<K, V> {
+    entries: Arc<Mutex<CacheEntries<K, V>>>,
+    capacity: usize,
+}
+
+#[derive(Debug)]
+struct CacheEntries<K, V> {
+    // Значение и номер последнего обращения к нему
+    values: HashMap<K, (V, u64)>,
+    clock: u64,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> GenerationCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(CacheEntries { values: HashMap::new(), clock: 0 })),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn get_or_insert_with(&self, key: K, compute: impl FnOnce() -> V) -> V {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            entries.clock += 1;
+            let clock = entries.clock;
+            if let Some((value, used)) = entries.values.get_mut(&key) {
+                *used = clock;
+                return value.clone();
+            }
+        }
+
+        let value = compute();
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+        entries.values.insert(key, (value.clone(), clock));
+        if entries.values.len() > self.capacity {
+            // Выбрасываем сразу четверть, чтобы перебор всего кеша случался редко
+            let keep = self.capacity * 3 / 4;
+            let mut used: Vec<u64> = entries.values.values().map(|(_, used)| *used).collect();
+            let cutoff = *used.select_nth_unstable(entries.values.len() - keep).1;
+            entries.values.retain(|_, (_, used)| *used >= cutoff);
+        }
+        value
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TerrainGenerator {
+    seed: u64,
+    settings: TerrainSettings,
+    elevation: Fbm<Perlin>,
+    temperature: Fbm<Perlin>,
+    humidity: Fbm<Perlin>,
+    roads: RoadCache,
+    rivers: RiverCache,
+}
+
+impl TerrainGenerator {
+    pub fn new(seed: u64, settings: TerrainSettings) -> Self {
+        // Каждому слою свой сид, иначе температура повторяла бы рельеф
+        let layer_seed = |layer: u64| splitmix64(seed ^ layer) as u32;
+
+        Self {
+            seed,
+            elevation: settings.elevation.build(layer_seed(1)),
+            temperature: settings.temperature.build(layer_seed(2)),
+            humidity: settings.humidity.build(layer_seed(3)),
+            settings,
+            roads: RoadCache::new(GENERATION_CACHE_CAPACITY),
+            rivers: RiverCache::new(GENERATION_CACHE_CAPACITY),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn settings(&self) -> &TerrainSettings {
+        &self.settings
+    }
+
+    pub fn road_cache(&self) -> &RoadCache {
+        &self.roads
+    }
+
+    pub fn river_cache(&self) -> &RiverCache {
+        &self.rivers
+    }
+
+    // Координаты в тайлах, так что соседние чанки сшиваются без швов
+    pub fn sample(&self, world_x: i32, world_y: i32) -> TerrainSample {
+        let point = [world_x as f64, world_y as f64];
+
+        TerrainSample {
+            elevation: normalize(self.elevation.get(point)),
+            temperature: normalize(self.temperature.get(point)),
+            humidity: normalize(self.humidity.get(point)),
+        }
+    }
+
+    // Только высота, без климата: рекам и дорогам остальное не нужно
+    pub fn elevation_at(&self, world_x: i32, world_y: i32) -> f32 {
+        normalize(self.elevation.get([world_x as f64, world_y as f64]))
+    }
+
+    // Озёра: всё, что ниже уровня воды
+    pub fn is_water(&self, sample: &TerrainSample) -> bool {
+        sample.elevation < self.settings.water_level
+    }
+
+    pub fn is_water_at(&self, world_x: i32, world_y: i32) -> bool {
+        self.elevation_at(world_x, world_y) < self.settings.water_level
+    }
+}
+
+// fBm выдаёт примерно -1..1
+fn normalize(value: f64) -> f32 {
+    (value as f32 * 0.5 + 0.5).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_computes_once() {
+        let cache = GenerationCache::new(4);
+        let mut calls = 0;
+        for _ in 0..3 {
+            assert_eq!(cache.get_or_insert_with(7, || { calls += 1; 49 }), 49);
+        }
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn cache_stays_bounded_and_keeps_recent_keys() {
+        let cache = GenerationCache::new(8);
+        for key in 0..100 {
+            cache.get_or_insert_with(key, || key * 2);
+            // Ключ 0 нужен постоянно и не должен вытесняться
+            cache.get_or_insert_with(0, || unreachable!());
+            assert!(cache.entries.lock().unwrap().values.len() <= 8);
+        }
+        assert!(cache.entries.lock().unwrap().values.contains_key(&99));
+    }
+}
@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use crate::game::biome::{BiomeDefinition, BiomeType};
+use crate::game::generate_map::TileType;
+use crate::game::map::MapState;
+use crate::game::tilemap::ChunkTiles;
+use crate::game::world::TilePos;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TileInfo {
+    pub tile_type: TileType,
+    pub biome: BiomeType,
+    pub walkable: bool,
+}
+
+// Что лежит в заданной точке мира. Данные берутся из тайлов загруженных чанков,
+// для незагруженных чанков ответа нет
+#[derive(SystemParam)]
+pub struct TileQuery<'w, 's> {
+    map_state: Res<'w, MapState>,
+    chunks: Query<'w, 's, &'static ChunkTiles>,
+}
+
+impl TileQuery<'_, '_> {
+    pub fn map_state(&self) -> &MapState {
+        &self.map_state
+    }
+
+    pub fn tile(&self, position: TilePos) -> Option<TileInfo> {
+        let entity = self.map_state.chunk_entity(position.chunk())?;
+        let tile = self.chunks.get(entity).ok()?.tile(position)?;
+
+        Some(TileInfo {
+            tile_type: tile.tile_type,
+            biome: tile.biome,
+            walkable: tile.tile_type.is_walkable(),
+        })
+    }
+
+    pub fn tile_at_world(&self, world: Vec2) -> Option<TileInfo> {
+        self.tile(TilePos::from_world(world))
+    }
+
+    // По незагруженным тайлам ходить нельзя
+    pub fn is_walkable(&self, position: TilePos) -> bool {
+        self.tile(position).is_some_and(|tile| tile.walkable)
+    }
+
+    pub fn biome(&self, position: TilePos) -> Option<&BiomeDefinition> {
+        let tile = self.tile(position)?;
+        Some(self.map_state.biomes().get(tile.biome))
+    }
+}
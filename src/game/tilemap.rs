@@ -0,0 +1,278 @@
+use bevy::prelude::*;
+use bevy::asset::LoadState;
+use bevy::render::mesh::Indices;
+use bevy::render::render_resource::PrimitiveTopology;
+use bevy::sprite::{Mesh2dHandle, TextureAtlasBuilder};
+use std::collections::HashMap;
+use crate::game::biome::BiomeRegistry;
+use crate::game::generate_map::{Tile, TileType};
+use crate::game::roads::RoadPiece;
+use crate::game::world::{TilePos, TILE_SIZE};
+
+// Тайлы, которые не зависят от биома
+const COMMON_TEXTURES: [&str; 9] = [
+    "common/road.png",
+    "common/road_corner.png",
+    "common/road_t.png",
+    "common/road_cross.png",
+    "common/road_2.png",
+    "common/road_2_corner.png",
+    "common/road_2_t.png",
+    "common/road_2_cross.png",
+    "common/bridge.png",
+];
+
+// Все текстуры тайлов собраны в один атлас, чтобы чанк рисовался одним мешем
+#[derive(Resource, Default)]
+pub struct TileAtlas {
+    // Текстуры, которые ещё грузятся
+    loading: Vec<(String, Handle<Image>)>,
+    material: Option<Handle<ColorMaterial>>,
+    // UV-прямоугольники текстур по их путям
+    uvs: HashMap<String, Rect>,
+}
+
+// Как рисуются тайлы. Спрайт на каждый тайл, как было до атласа, остался только для замера
+// производительности в debug::tile_benchmark
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TileRenderMode {
+    #[default]
+    Mesh,
+    Sprites,
+}
+
+#[derive(Component)]
+pub struct TileSprite;
+
+// Тайлы чанка. Меш перестраивается при любом изменении компонента
+#[derive(Component)]
+pub struct ChunkTiles {
+    pub tiles: Vec<Tile>,
+}
+
+impl ChunkTiles {
+    pub fn tile(&self, tile: TilePos) -> Option<&Tile> {
+        let chunk_pos = self.tiles.first()?.position.chunk();
+        self.tiles.get(chunk_pos.tile_index(tile)?)
+    }
+}
+
+impl TileAtlas {
+    pub fn load(asset_server: &AssetServer, biomes: &BiomeRegistry) -> Self {
+        let mut paths: Vec<String> = COMMON_TEXTURES.iter().map(|path| path.to_string()).collect();
+        for biome in biomes.iter() {
+            let textures = &biome.textures;
+            paths.extend(textures.grass.iter().cloned());
+            paths.push(textures.water.clone());
+            paths.extend(textures.dirt.iter().cloned());
+            paths.extend(textures.shore.iter().map(|shore| shore.texture.clone()));
+            paths.extend(biome.borders.values().map(|border| border.texture.clone()));
+        }
+        paths.sort();
+        paths.dedup();
+
+        Self {
+            loading: paths
+                .into_iter()
+                .map(|path| {
+                    let handle = asset_server.load(path.as_str());
+                    (path, handle)
+                })
+                .collect(),
+            material: None,
+            uvs: HashMap::new(),
+        }
+    }
+
+    pub fn material(&self) -> Option<&Handle<ColorMaterial>> {
+        self.material.as_ref()
+    }
+
+    fn uv_rect(&self, texture: &str) -> Rect {
+        self.uvs.get(texture).copied().unwrap_or_default()
+    }
+}
+
+// Собирает атлас, как только загрузились все текстуры тайлов
+pub fn build_tile_atlas(
+    asset_server: Res<AssetServer>,
+    mut atlas: ResMut<TileAtlas>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if atlas.material.is_some() || atlas.loading.is_empty() {
+        return;
+    }
+
+    // Битые текстуры выкидываем, вместо них будет пустой прямоугольник
+    atlas.loading.retain(|(path, handle)| {
+        let failed = asset_server.get_load_state(handle) == LoadState::Failed;
+        if failed {
+            println!("Не удалось загрузить текстуру тайла: {}", path);
+        }
+        !failed
+    });
+    let load_state = asset_server.get_group_load_state(atlas.loading.iter().map(|(_, handle)| handle.id()));
+    if load_state != LoadState::Loaded {
+        return;
+    }
+
+    let mut builder = TextureAtlasBuilder::default();
+    for (_, handle) in &atlas.loading {
+        if let Some(image) = images.get(handle) {
+            builder.add_texture(handle.clone(), image);
+        }
+    }
+    let texture_atlas = match builder.finish(&mut images) {
+        Ok(texture_atlas) => texture_atlas,
+        Err(err) => {
+            println!("Не удалось собрать атлас тайлов: {:?}", err);
+            atlas.loading.clear();
+            return;
+        }
+    };
+
+    // Сжимаем прямоугольник на полтекселя, чтобы фильтрация не цепляла соседние текстуры
+    let mut uvs = HashMap::new();
+    for (path, handle) in atlas.loading.drain(..) {
+        if let Some(index) = texture_atlas.get_texture_index(&handle) {
+            let rect = texture_atlas.textures[index];
+            uvs.insert(path, Rect {
+                min: (rect.min + 0.5) / texture_atlas.size,
+                max: (rect.max - 0.5) / texture_atlas.size,
+            });
+        }
+    }
+    atlas.uvs = uvs;
+    atlas.material = Some(materials.add(ColorMaterial::from(texture_atlas.texture)));
+}
+
+// Перестраивает меш чанка на месте, не пересоздавая сущностей
+pub fn update_chunk_meshes(
+    map_state: Res<crate::game::map::MapState>,
+    atlas: Res<TileAtlas>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    query: Query<(&ChunkTiles, &Mesh2dHandle), Changed<ChunkTiles>>,
+) {
+    for (chunk_tiles, mesh_handle) in query.iter() {
+        if let Some(mesh) = meshes.get_mut(&mesh_handle.0) {
+            *mesh = build_chunk_mesh(&chunk_tiles.tiles, map_state.biomes(), &atlas);
+        }
+    }
+}
+
+// Старая отрисовка для замера: меш у чанка отбирается, и каждый тайл рисуется своим спрайтом
+// со своей текстурой. Изменения тайлов после спавна здесь не отслеживаются
+pub fn spawn_tile_sprites(
+    mut commands: Commands,
+    mode: Res<TileRenderMode>,
+    asset_server: Res<AssetServer>,
+    map_state: Res<crate::game::map::MapState>,
+    query: Query<(Entity, &ChunkTiles), Added<ChunkTiles>>,
+) {
+    if *mode != TileRenderMode::Sprites {
+        return;
+    }
+
+    for (chunk, chunk_tiles) in query.iter() {
+        commands.entity(chunk).remove::<Mesh2dHandle>().with_children(|parent| {
+            for tile in &chunk_tiles.tiles {
+                let (texture, rotation) = tile_texture(tile, map_state.biomes());
+                let position = tile.position.to_world();
+                parent.spawn((
+                    SpriteBundle {
+                        transform: Transform::from_xyz(position.x, position.y, 0.0)
+                            .with_rotation(Quat::from_rotation_z(rotation.to_radians())),
+                        texture: asset_server.load(texture),
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::splat(TILE_SIZE)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    TileSprite,
+                ));
+            }
+        });
+    }
+}
+
+pub fn empty_chunk_mesh() -> Mesh {
+    Mesh::new(PrimitiveTopology::TriangleList)
+}
+
+fn build_chunk_mesh(tiles: &[Tile], biomes: &BiomeRegistry, atlas: &TileAtlas) -> Mesh {
+    let mut positions = Vec::with_capacity(tiles.len() * 4);
+    let mut normals = Vec::with_capacity(tiles.len() * 4);
+    let mut uvs = Vec::with_capacity(tiles.len() * 4);
+    let mut indices = Vec::with_capacity(tiles.len() * 6);
+
+    for tile in tiles {
+        let (texture, rotation) = tile_texture(tile, biomes);
+        let rect = atlas.uv_rect(texture);
+        // Углы против часовой стрелки, начиная с левого нижнего
+        let corners = [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)];
+        let corner_uvs = [
+            [rect.min.x, rect.max.y],
+            [rect.max.x, rect.max.y],
+            [rect.max.x, rect.min.y],
+            [rect.min.x, rect.min.y],
+        ];
+        // Тайлы поворачиваются только на четверти оборота, поэтому вращаем UV,
+        // а вершины соседних тайлов остаются в точности совпадающими
+        let quarter = (rotation / 90.0).round() as i32;
+
+        let base = positions.len() as u32;
+        for (index, (dx, dy)) in corners.into_iter().enumerate() {
+            let center = tile.position.to_world();
+            positions.push([center.x + dx * TILE_SIZE, center.y + dy * TILE_SIZE, 0.0]);
+            normals.push([0.0, 0.0, 1.0]);
+            uvs.push(corner_uvs[(index as i32 - quarter).rem_euclid(4) as usize]);
+        }
+        indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+// Текстура тайла и её поворот в градусах
+fn tile_texture<'a>(tile: &Tile, biomes: &'a BiomeRegistry) -> (&'a str, f32) {
+    let biome = biomes.get(tile.biome);
+    match tile.tile_type {
+        TileType::Grass { rotation, variant } => (biome.grass_texture(variant), rotation),
+        TileType::Water => (biome.textures.water.as_str(), 0.0),
+        TileType::Dirt => (biome.dirt_texture(), 0.0),
+        TileType::Road { piece, rotation, variant } => (road_texture(piece, variant), rotation),
+        TileType::Bridge { rotation } => ("common/bridge.png", rotation),
+        TileType::Shore { rotation } => {
+            match &biome.textures.shore {
+                Some(shore) => (shore.texture.as_str(), rotation + shore.rotation),
+                None => (biome.grass_texture(0), 0.0),
+            }
+        },
+        TileType::BiomeBorder { from, to, rotation } => {
+            match biomes.border_texture(from, to) {
+                Some(border) => (border.texture.as_str(), rotation + border.rotation),
+                None => (biome.grass_texture(0), 0.0),
+            }
+        },
+    }
+}
+
+fn road_texture(piece: RoadPiece, variant: u8) -> &'static str {
+    match (piece, variant) {
+        (RoadPiece::Straight, 0) => "common/road.png",
+        (RoadPiece::Corner, 0) => "common/road_corner.png",
+        (RoadPiece::Junction, 0) => "common/road_t.png",
+        (RoadPiece::Crossing, 0) => "common/road_cross.png",
+        (RoadPiece::Straight, _) => "common/road_2.png",
+        (RoadPiece::Corner, _) => "common/road_2_corner.png",
+        (RoadPiece::Junction, _) => "common/road_2_t.png",
+        (RoadPiece::Crossing, _) => "common/road_2_cross.png",
+    }
+}
@@ -0,0 +1,422 @@
+use bevy::prelude::*;
+use bevy::input::mouse::MouseWheel;
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use crate::game::bullet::{spawn_bullet, CursorPosition};
+use crate::game::data::{assets_dir, load_ron_dir};
+use crate::game::health::Dead;
+use crate::game::menu::GameState;
+use crate::game::player::Player;
+
+const DEFAULT_BULLET_SPEED: f32 = 600.0;
+const DEFAULT_BULLET_LIFETIME: f32 = 1.5;
+
+const SLOT_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+// Индекс оружия в реестре, оружие отсортировано по номеру слота
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct WeaponType(pub u16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum FireMode {
+    // Один выстрел на нажатие
+    Semi,
+    // Стреляет, пока кнопка зажата
+    Auto,
+    // Очередь из заданного числа выстрелов на нажатие
+    Burst(u32),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WeaponDefinition {
+    pub name: String,
+    // Клавиша выбора оружия, от 1 до 9
+    pub slot: u8,
+    // Урон одной пули
+    pub damage: f32,
+    // Выстрелов в секунду
+    pub fire_rate: f32,
+    // Полный угол разброса в градусах
+    pub spread: f32,
+    // Пуль за выстрел, больше одной у дробовиков
+    pub pellets: u32,
+    pub magazine: u32,
+    // Время перезарядки в секундах
+    pub reload_time: f32,
+    pub mode: FireMode,
+    #[serde(default = "default_bullet_speed")]
+    pub bullet_speed: f32,
+    #[serde(default = "default_bullet_lifetime")]
+    pub bullet_lifetime: f32,
+}
+
+fn default_bullet_speed() -> f32 {
+    DEFAULT_BULLET_SPEED
+}
+
+fn default_bullet_lifetime() -> f32 {
+    DEFAULT_BULLET_LIFETIME
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct WeaponRegistry {
+    weapons: Vec<WeaponDefinition>,
+}
+
+impl WeaponRegistry {
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        Self::from_definitions(load_ron_dir(dir)?)
+    }
+
+    pub fn from_definitions(mut weapons: Vec<WeaponDefinition>) -> Result<Self, String> {
+        if weapons.is_empty() {
+            return Err("не задано ни одного оружия".to_string());
+        }
+        weapons.sort_by_key(|weapon| weapon.slot);
+
+        for (index, weapon) in weapons.iter().enumerate() {
+            if !(1..=SLOT_KEYS.len() as u8).contains(&weapon.slot) {
+                return Err(format!("оружие {}: слот {} вне диапазона 1..9", weapon.name, weapon.slot));
+            }
+            if index > 0 && weapons[index - 1].slot == weapon.slot {
+                return Err(format!("оружие {} и {} в одном слоте", weapons[index - 1].name, weapon.name));
+            }
+            if weapon.fire_rate <= 0.0 || weapon.magazine == 0 || weapon.pellets == 0 {
+                return Err(format!("оружие {}: темп стрельбы, магазин и число пуль должны быть больше нуля", weapon.name));
+            }
+        }
+
+        Ok(Self { weapons })
+    }
+
+    pub fn get(&self, weapon: WeaponType) -> &WeaponDefinition {
+        &self.weapons[weapon.0 as usize]
+    }
+
+    pub fn count(&self) -> usize {
+        self.weapons.len()
+    }
+
+    pub fn find_slot(&self, slot: u8) -> Option<WeaponType> {
+        self.weapons
+            .iter()
+            .position(|weapon| weapon.slot == slot)
+            .map(|index| WeaponType(index as u16))
+    }
+}
+
+// Оружие в руках и состояние магазинов всего арсенала
+#[derive(Component)]
+pub struct Weapon {
+    pub current: WeaponType,
+    // Патроны в магазинах, у ещё не тронутого оружия магазин полный
+    ammo: HashMap<WeaponType, u32>,
+    cooldown: Timer,
+    reload: Option<Timer>,
+    // Сколько выстрелов осталось в текущей очереди
+    burst_left: u32,
+}
+
+impl Default for Weapon {
+    fn default() -> Self {
+        Self {
+            current: WeaponType::default(),
+            ammo: HashMap::new(),
+            cooldown: Timer::new(Duration::ZERO, TimerMode::Once),
+            reload: None,
+            burst_left: 0,
+        }
+    }
+}
+
+impl Weapon {
+    pub fn ammo(&self, registry: &WeaponRegistry) -> u32 {
+        self.ammo.get(&self.current).copied().unwrap_or(registry.get(self.current).magazine)
+    }
+
+    // Доля выполненной перезарядки, None если не перезаряжаемся
+    pub fn reload_progress(&self) -> Option<f32> {
+        self.reload.as_ref().map(|timer| timer.percent())
+    }
+
+    fn switch_to(&mut self, weapon: WeaponType) {
+        if weapon != self.current {
+            self.current = weapon;
+            self.reload = None;
+            self.burst_left = 0;
+        }
+    }
+
+    fn start_reload(&mut self, registry: &WeaponRegistry) {
+        let definition = registry.get(self.current);
+        if self.reload.is_none() && self.ammo(registry) < definition.magazine {
+            self.reload = Some(Timer::from_seconds(definition.reload_time, TimerMode::Once));
+            self.burst_left = 0;
+        }
+    }
+
+    // Очередь, начатая одним нажатием, достреливается без нажатий
+    fn wants_to_fire(&self, definition: &WeaponDefinition, pressed: bool, just_pressed: bool) -> bool {
+        self.burst_left > 0
+            || match definition.mode {
+                FireMode::Auto => pressed,
+                FireMode::Semi | FireMode::Burst(_) => just_pressed,
+            }
+    }
+
+    // Тратит патрон на выстрел, если оружие готово. Пустой магазин перезаряжается при попытке выстрелить
+    fn fire(&mut self, registry: &WeaponRegistry) -> bool {
+        if self.reload.is_some() || !self.cooldown.finished() {
+            return false;
+        }
+        let ammo = self.ammo(registry);
+        if ammo == 0 {
+            self.start_reload(registry);
+            return false;
+        }
+
+        let definition = registry.get(self.current);
+        self.ammo.insert(self.current, ammo - 1);
+        self.burst_left = match definition.mode {
+            FireMode::Burst(shots) if self.burst_left == 0 => shots.saturating_sub(1),
+            _ => self.burst_left.saturating_sub(1),
+        };
+        self.cooldown = Timer::from_seconds(1.0 / definition.fire_rate, TimerMode::Once);
+        true
+    }
+}
+
+#[derive(Component)]
+pub struct WeaponHud;
+
+pub fn setup_weapons(mut commands: Commands) {
+    let registry = WeaponRegistry::load(&assets_dir().join("weapons"))
+        .unwrap_or_else(|err| panic!("Не удалось загрузить оружие: {}", err));
+    commands.insert_resource(registry);
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 24.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            ..default()
+        }),
+        WeaponHud,
+    ));
+}
+
+// Смена оружия клавишами 1-9 и колесом мыши. В режиме строительства колесо выбирает постройку
+pub fn switch_weapon(
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    mut wheel_events: EventReader<MouseWheel>,
+    registry: Res<WeaponRegistry>,
+    mut weapon_query: Query<&mut Weapon, (With<Player>, Without<Dead>)>,
+) {
+    let Ok(mut weapon) = weapon_query.get_single_mut() else {
+        return;
+    };
+
+    for (slot, key) in SLOT_KEYS.iter().enumerate() {
+        if keyboard.just_pressed(*key) {
+            if let Some(kind) = registry.find_slot(slot as u8 + 1) {
+                weapon.switch_to(kind);
+            }
+        }
+    }
+
+    let scroll: f32 = wheel_events.iter().map(|event| event.y).sum();
+    if scroll != 0.0 && !game_state.building {
+        let count = registry.count() as i32;
+        let step = if scroll > 0.0 { 1 } else { -1 };
+        let next = (weapon.current.0 as i32 + step).rem_euclid(count);
+        weapon.switch_to(WeaponType(next as u16));
+    }
+}
+
+// Задержка между выстрелами и перезарядка идут по игровому времени
+pub fn tick_weapons(
+    time: Res<Time>,
+    registry: Res<WeaponRegistry>,
+    mut weapon_query: Query<&mut Weapon>,
+) {
+    for mut weapon in weapon_query.iter_mut() {
+        weapon.cooldown.tick(time.delta());
+
+        let reloaded = weapon.reload.as_mut().is_some_and(|reload| reload.tick(time.delta()).finished());
+        if reloaded {
+            let current = weapon.current;
+            weapon.ammo.insert(current, registry.get(current).magazine);
+            weapon.reload = None;
+        }
+    }
+}
+
+pub fn player_shoot(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    registry: Res<WeaponRegistry>,
+    cursor: CursorPosition,
+    mut player_query: Query<(Entity, &Transform, &mut Weapon), (With<Player>, Without<Dead>)>,
+) {
+    let Ok((entity, transform, mut weapon)) = player_query.get_single_mut() else {
+        return;
+    };
+    let definition = registry.get(weapon.current);
+
+    // Клики по меню паузы и по инвентарю не должны стрелять
+    if game_state.paused || game_state.pointer_over_ui || game_state.building || weapon.reload.is_some() {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::R) {
+        weapon.start_reload(&registry);
+        return;
+    }
+
+    if !weapon.wants_to_fire(definition, mouse.pressed(MouseButton::Left), mouse.just_pressed(MouseButton::Left)) {
+        return;
+    }
+
+    let Some(cursor) = cursor.world_position() else {
+        return;
+    };
+    let position = transform.translation.truncate();
+    let Some(direction) = (cursor - position).try_normalize() else {
+        return;
+    };
+    if !weapon.fire(&registry) {
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    let half_spread = definition.spread.to_radians() * 0.5;
+    for _ in 0..definition.pellets {
+        let angle = if half_spread > 0.0 { rng.gen_range(-half_spread..=half_spread) } else { 0.0 };
+        let velocity = Vec2::from_angle(angle).rotate(direction) * definition.bullet_speed;
+        spawn_bullet(&mut commands, entity, position, velocity, definition.damage, definition.bullet_lifetime);
+    }
+}
+
+pub fn weapon_hud(
+    registry: Res<WeaponRegistry>,
+    weapon_query: Query<&Weapon, With<Player>>,
+    mut hud_query: Query<&mut Text, With<WeaponHud>>,
+) {
+    let (Ok(weapon), Ok(mut text)) = (weapon_query.get_single(), hud_query.get_single_mut()) else {
+        return;
+    };
+    let definition = registry.get(weapon.current);
+
+    text.sections[0].value = match weapon.reload_progress() {
+        Some(progress) => format!("{}: перезарядка {:.0}%", definition.name, progress * 100.0),
+        None => format!("{}: {}/{}", definition.name, weapon.ammo(&registry), definition.magazine),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weapon(name: &str, slot: u8, mode: FireMode) -> WeaponDefinition {
+        WeaponDefinition {
+            name: name.to_string(),
+            slot,
+            damage: 10.0,
+            fire_rate: 10.0,
+            spread: 0.0,
+            pellets: 1,
+            magazine: 5,
+            reload_time: 1.0,
+            mode,
+            bullet_speed: DEFAULT_BULLET_SPEED,
+            bullet_lifetime: DEFAULT_BULLET_LIFETIME,
+        }
+    }
+
+    // Оружие с готовым к выстрелу таймером, как после tick_weapons
+    fn ready(weapon: &mut Weapon) {
+        let duration = weapon.cooldown.duration();
+        weapon.cooldown.tick(duration);
+    }
+
+    #[test]
+    fn slot_outside_range_is_rejected() {
+        assert!(WeaponRegistry::from_definitions(vec![weapon("pistol", 0, FireMode::Semi)]).is_err());
+        assert!(WeaponRegistry::from_definitions(vec![weapon("pistol", 10, FireMode::Semi)]).is_err());
+        assert!(WeaponRegistry::from_definitions(vec![weapon("pistol", 9, FireMode::Semi)]).is_ok());
+    }
+
+    #[test]
+    fn duplicate_slot_is_rejected() {
+        let weapons = vec![weapon("pistol", 2, FireMode::Semi), weapon("rifle", 2, FireMode::Auto)];
+        assert!(WeaponRegistry::from_definitions(weapons).is_err());
+    }
+
+    #[test]
+    fn burst_fires_its_shot_count_per_press() {
+        let registry = WeaponRegistry::from_definitions(vec![weapon("carbine", 1, FireMode::Burst(3))]).unwrap();
+        let definition = registry.get(WeaponType(0));
+        let mut weapon = Weapon::default();
+
+        let mut shots = 0;
+        let mut pressed = true;
+        while weapon.wants_to_fire(definition, pressed, pressed) {
+            ready(&mut weapon);
+            assert!(weapon.fire(&registry));
+            shots += 1;
+            pressed = false;
+        }
+        assert_eq!(shots, 3, "одно нажатие должно дать очередь целиком");
+        assert_eq!(weapon.ammo(&registry), 2);
+    }
+
+    #[test]
+    fn shot_takes_one_round_from_magazine() {
+        let registry = WeaponRegistry::from_definitions(vec![weapon("pistol", 1, FireMode::Semi)]).unwrap();
+        let mut weapon = Weapon::default();
+
+        ready(&mut weapon);
+        assert!(weapon.fire(&registry));
+        assert_eq!(weapon.ammo(&registry), 4);
+        assert!(!weapon.fire(&registry), "до конца задержки второй выстрел невозможен");
+        assert_eq!(weapon.ammo(&registry), 4);
+    }
+
+    #[test]
+    fn empty_magazine_reloads_on_fire() {
+        let registry = WeaponRegistry::from_definitions(vec![weapon("rifle", 1, FireMode::Auto)]).unwrap();
+        let mut weapon = Weapon::default();
+        for _ in 0..5 {
+            ready(&mut weapon);
+            assert!(weapon.fire(&registry));
+        }
+        assert_eq!(weapon.ammo(&registry), 0);
+
+        ready(&mut weapon);
+        assert!(!weapon.fire(&registry), "из пустого магазина не стреляют");
+        assert!(weapon.reload_progress().is_some(), "пустой магазин должен начать перезарядку");
+    }
+}
@@ -0,0 +1,118 @@
+use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+
+// Размер чанка в тайлах
+pub const CHUNK_SIZE: i32 = 16;
+// Размер тайла в мировых единицах (пикселях)
+pub const TILE_SIZE: f32 = 32.0;
+pub const CHUNK_WORLD_SIZE: f32 = CHUNK_SIZE as f32 * TILE_SIZE;
+
+// Координаты тайла в мире. Центр тайла (x, y) лежит в мировой точке (x, y) * TILE_SIZE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TilePos(pub i32, pub i32);
+
+// Координаты чанка: чанк (cx, cy) содержит тайлы от (cx, cy) * CHUNK_SIZE до следующего чанка
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChunkPosition(pub i32, pub i32);
+
+impl TilePos {
+    // Тайл, которому принадлежит точка мира
+    pub fn from_world(world: Vec2) -> Self {
+        Self(
+            (world.x / TILE_SIZE + 0.5).floor() as i32,
+            (world.y / TILE_SIZE + 0.5).floor() as i32,
+        )
+    }
+
+    // Центр тайла в мире
+    pub fn to_world(self) -> Vec2 {
+        Vec2::new(self.0 as f32, self.1 as f32) * TILE_SIZE
+    }
+
+    // div_euclid округляет вниз и для отрицательных координат: тайл -1 лежит в чанке -1
+    pub fn chunk(self) -> ChunkPosition {
+        ChunkPosition(self.0.div_euclid(CHUNK_SIZE), self.1.div_euclid(CHUNK_SIZE))
+    }
+
+    // Координаты внутри чанка, всегда в 0..CHUNK_SIZE
+    pub fn local(self) -> (i32, i32) {
+        (self.0.rem_euclid(CHUNK_SIZE), self.1.rem_euclid(CHUNK_SIZE))
+    }
+
+    pub fn offset(self, dx: i32, dy: i32) -> Self {
+        Self(self.0 + dx, self.1 + dy)
+    }
+}
+
+impl ChunkPosition {
+    pub fn from_world(world: Vec2) -> Self {
+        TilePos::from_world(world).chunk()
+    }
+
+    // Левый нижний тайл чанка
+    pub fn origin(self) -> TilePos {
+        TilePos(self.0 * CHUNK_SIZE, self.1 * CHUNK_SIZE)
+    }
+
+    pub fn tile(self, local_x: i32, local_y: i32) -> TilePos {
+        self.origin().offset(local_x, local_y)
+    }
+
+    // Центр чанка в мире
+    pub fn center(self) -> Vec2 {
+        self.origin().to_world() + Vec2::splat((CHUNK_SIZE as f32 - 1.0) * 0.5 * TILE_SIZE)
+    }
+
+    // Индекс тайла в ChunkData::tiles, тайлы лежат по строкам снизу вверх
+    pub fn tile_index(self, tile: TilePos) -> Option<usize> {
+        if tile.chunk() != self {
+            return None;
+        }
+        let (local_x, local_y) = tile.local();
+        Some((local_y * CHUNK_SIZE + local_x) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Граница между тайлами 0 и -1 проходит по -0.5 тайла и принадлежит тайлу 0
+    #[test]
+    fn from_world_rounds_to_nearest_tile() {
+        assert_eq!(TilePos::from_world(Vec2::splat(0.0)), TilePos(0, 0));
+        assert_eq!(TilePos::from_world(Vec2::splat(-0.5 * TILE_SIZE)), TilePos(0, 0));
+        assert_eq!(TilePos::from_world(Vec2::splat(-0.51 * TILE_SIZE)), TilePos(-1, -1));
+        assert_eq!(TilePos::from_world(Vec2::new(0.49 * TILE_SIZE, 1.51 * TILE_SIZE)), TilePos(0, 2));
+    }
+
+    #[test]
+    fn negative_tiles_belong_to_negative_chunks() {
+        assert_eq!(TilePos(-1, 5).chunk(), ChunkPosition(-1, 0));
+        assert_eq!(TilePos(-16, -1).chunk(), ChunkPosition(-1, -1));
+        assert_eq!(TilePos(-17, -16).chunk(), ChunkPosition(-2, -1));
+        assert_eq!(TilePos(15, 16).chunk(), ChunkPosition(0, 1));
+    }
+
+    #[test]
+    fn local_is_inside_chunk() {
+        for y in -40..40 {
+            for x in -40..40 {
+                let tile = TilePos(x, y);
+                let (local_x, local_y) = tile.local();
+                assert!((0..CHUNK_SIZE).contains(&local_x) && (0..CHUNK_SIZE).contains(&local_y), "{:?}", tile);
+                assert_eq!(tile.chunk().tile(local_x, local_y), tile);
+            }
+        }
+    }
+
+    #[test]
+    fn tile_index_only_for_own_tiles() {
+        let chunk_pos = ChunkPosition(-1, 2);
+        assert_eq!(chunk_pos.tile_index(chunk_pos.origin()), Some(0));
+        assert_eq!(chunk_pos.tile_index(chunk_pos.tile(CHUNK_SIZE - 1, CHUNK_SIZE - 1)), Some((CHUNK_SIZE * CHUNK_SIZE - 1) as usize));
+        assert_eq!(chunk_pos.tile_index(chunk_pos.tile(CHUNK_SIZE, 0)), None);
+        assert_eq!(chunk_pos.tile_index(chunk_pos.tile(0, -1)), None);
+        assert_eq!(chunk_pos.tile_index(TilePos(0, 40)), None);
+    }
+}
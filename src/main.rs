@@ -1,11 +1,31 @@
+// Запросы систем Bevy с фильтрами всегда выглядят сложными, выносить их в type не нужно
+#![allow(clippy::type_complexity)]
+
 mod game;
 
 use bevy::prelude::*;
-use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
+use bevy::diagnostic::{EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin};
+use bevy_prototype_lyon::prelude::ShapePlugin;
 use game::player::{spawn_player, player_movement, camera_follow};
-use game::map::{setup_map, update_map};
-use game::debug::{setup_debug, debug_input, debug_ui};
-use game::menu::{setup_menu, pause_input, pause_menu, handle_buttons, GameState};
+use game::bullet::move_bullets;
+use game::health::{setup_health, apply_damage, tick_invulnerability, respawn_player, settle_spawn_point, health_hud, DamageEvent, RespawnPlayer};
+use game::survival::{setup_survival, update_survival, drink_water, reset_survival, survival_hud};
+use game::item::setup_items;
+use game::inventory::{pickup_items, drop_items, use_item, update_item_chunks, DropItem};
+use game::inventory_ui::{setup_inventory_ui, toggle_inventory, inventory_mouse, update_slots, update_pointer_ui};
+use game::station::use_bed;
+use game::crafting::{setup_crafting, craft_buttons, tick_crafting, update_crafting_ui};
+use game::building::{setup_building, build_mode_input, update_ghost, build_input, mark_damaged_structures, build_hud};
+use game::enemy::{setup_enemies, enemy_ai};
+use game::spawning::{setup_spawning, spawn_mobs, update_mob_chunks};
+use game::clock::{tick_clock, WorldClock};
+use game::harvest::{harvest_input, apply_harvest_hits, respawn_decorations, animate_hits, HarvestHit};
+use game::weapon::{setup_weapons, switch_weapon, tick_weapons, player_shoot, weapon_hud};
+use game::map::{setup_map, update_map, spawn_generated_chunks, update_chunk_activity, write_chunks, reload_world_data, y_sort, ChunkSpawned};
+use game::tilemap::{build_tile_atlas, update_chunk_meshes, spawn_tile_sprites, TileRenderMode};
+use game::save::{setup_save, autosave, save_game, load_game, SaveGame, LoadGame};
+use game::debug::{setup_debug, debug_input, debug_ui, admin_spawn_enemy, start_tile_benchmark, tile_benchmark};
+use game::menu::{setup_menu, pause_input, pause_menu, death_screen, handle_buttons, GameState};
 
 fn pause_system(
     game_state: Res<GameState>,
@@ -17,18 +37,83 @@ fn pause_system(
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
-        .add_plugins(FrameTimeDiagnosticsPlugin::default())
-        .add_systems(Startup, (setup_map, spawn_player, setup_debug, setup_menu))
+        .add_plugins(FrameTimeDiagnosticsPlugin)
+        .add_plugins(EntityCountDiagnosticsPlugin)
+        .add_plugins(ShapePlugin)
+        .init_resource::<TileRenderMode>()
+        .add_event::<SaveGame>()
+        .add_event::<LoadGame>()
+        .add_event::<DamageEvent>()
+        .add_event::<RespawnPlayer>()
+        .add_event::<ChunkSpawned>()
+        .add_event::<DropItem>()
+        .add_event::<HarvestHit>()
+        .init_resource::<WorldClock>()
+        .add_systems(Startup, (setup_map, spawn_player, setup_weapons, setup_health, setup_survival, setup_items, setup_inventory_ui, setup_save, setup_debug, setup_menu, setup_enemies))
+        .add_systems(PostStartup, (setup_crafting, setup_building, setup_spawning))
         .add_systems(Update, (
             player_movement,
             camera_follow,
-            update_map,
+            (switch_weapon, tick_weapons, player_shoot).chain(),
+            move_bullets,
+            weapon_hud,
+            (
+                (load_game, update_map, spawn_generated_chunks, update_chunk_activity).chain(),
+                write_chunks.after(update_map),
+                build_tile_atlas,
+                update_chunk_meshes,
+                spawn_tile_sprites,
+            ),
+            (start_tile_benchmark, tile_benchmark).chain().after(load_game).before(update_map),
+            reload_world_data.run_if(|| cfg!(debug_assertions)),
+            y_sort,
             debug_input,
             debug_ui,
             pause_input,
             pause_menu,
             handle_buttons,
             pause_system,
+            autosave,
+            save_game.after(handle_buttons),
+        ))
+        .add_systems(Update, (
+            apply_damage.after(move_bullets),
+            tick_invulnerability,
+            respawn_player.after(handle_buttons),
+            settle_spawn_point.before(spawn_generated_chunks),
+            death_screen,
+            health_hud,
+            (update_survival, drink_water).before(apply_damage),
+            use_bed,
+            reset_survival,
+            survival_hud,
+        ))
+        .add_systems(Update, (
+            (
+                toggle_inventory,
+                inventory_mouse,
+                drop_items,
+                pickup_items,
+                use_item,
+                craft_buttons,
+                tick_crafting,
+                update_slots,
+                update_pointer_ui,
+            ).chain(),
+            update_crafting_ui,
+            update_item_chunks.before(update_map),
+        ))
+        .add_systems(Update, (
+            tick_clock,
+            (harvest_input, apply_harvest_hits).chain(),
+            respawn_decorations,
+            animate_hits,
+            (build_mode_input, update_ghost, build_input, build_hud).chain(),
+            mark_damaged_structures.before(apply_damage),
+            enemy_ai.before(apply_damage),
+            admin_spawn_enemy,
+            spawn_mobs,
+            update_mob_chunks.before(update_map).before(apply_damage),
         ))
         .run();
 }
\ No newline at end of file